@@ -0,0 +1,44 @@
+//! Build script, generating the C API header when the `ffi` feature is enabled
+//! and compiling the C API tests with the (development only) `ffi-test` feature
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    ffi::build();
+}
+
+#[cfg(feature = "ffi")]
+mod ffi {
+    use std::env;
+    use std::path::PathBuf;
+
+    pub fn build() {
+        let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+        let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        println!("cargo:rerun-if-changed=tests/c");
+
+        // Generate C header, the committed `include/driver_pal.h` is checked
+        // against this by the `test_header` test
+        let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+            .expect("Error loading cbindgen.toml");
+
+        cbindgen::Builder::new()
+            .with_crate(&crate_dir)
+            .with_config(config)
+            .generate()
+            .expect("Error generating C header")
+            .write_to_file(out_dir.join("driver_pal.h"));
+
+        // Compile the C API test programs for use against the mock backend
+        if env::var_os("CARGO_FEATURE_FFI_TEST").is_some() {
+            cc::Build::new()
+                .file(crate_dir.join("tests/c/ffi_mock.c"))
+                .file(crate_dir.join("tests/c/example_driver.c"))
+                .include(&out_dir)
+                .warnings_into_errors(true)
+                .compile("driver_pal_ctest");
+        }
+    }
+}
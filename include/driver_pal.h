@@ -0,0 +1,334 @@
+#ifndef DRIVER_PAL_H
+#define DRIVER_PAL_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/*
+ Success return code
+ */
+#define DRIVER_PAL_OK 0
+
+/*
+ Generic failure return code
+ */
+#define DRIVER_PAL_ERR -1
+
+/*
+ Null handle or buffer return code
+ */
+#define DRIVER_PAL_ERR_NULL -2
+
+/*
+ Invalid or mismatched handle return code
+ */
+#define DRIVER_PAL_ERR_HANDLE -3
+
+/*
+ Panic caught at the FFI boundary return code
+ */
+#define DRIVER_PAL_ERR_PANIC -4
+
+/*
+ SPI bus failure return code
+ */
+#define DRIVER_PAL_ERR_SPI -5
+
+/*
+ GPIO pin failure return code
+ */
+#define DRIVER_PAL_ERR_PIN -6
+
+/*
+ Operation aborted return code
+ */
+#define DRIVER_PAL_ERR_ABORTED -7
+
+/*
+ Operation timeout return code
+ */
+#define DRIVER_PAL_ERR_TIMEOUT -8
+
+/*
+ Pin not configured return code
+ */
+#define DRIVER_PAL_ERR_NO_PIN -9
+
+/*
+ No matching driver return code
+ */
+#define DRIVER_PAL_ERR_NO_DRIVER -10
+
+/*
+ Invalid configuration return code
+ */
+#define DRIVER_PAL_ERR_CONFIG -11
+
+/*
+ Operating system or USB I/O failure return code
+ */
+#define DRIVER_PAL_ERR_IO -12
+
+/*
+ Backend device failure return code
+ */
+#define DRIVER_PAL_ERR_BACKEND -13
+
+/*
+ Invalid argument return code
+ */
+#define DRIVER_PAL_ERR_INVALID_ARG -14
+
+/*
+ Reset pin identifier for `driver_pal_vtable_t` GPIO functions
+ */
+#define DRIVER_PAL_PIN_RESET 0
+
+/*
+ Busy pin identifier for `driver_pal_vtable_t` GPIO functions
+ */
+#define DRIVER_PAL_PIN_BUSY 1
+
+/*
+ Ready pin identifier for `driver_pal_vtable_t` GPIO functions
+ */
+#define DRIVER_PAL_PIN_READY 2
+
+/*
+ Write operation, writing `len` bytes from `tx`
+ */
+#define DRIVER_PAL_OP_WRITE 0
+
+/*
+ Read operation, reading `len` bytes into `rx`
+ */
+#define DRIVER_PAL_OP_READ 1
+
+/*
+ Transfer operation, writing `len` bytes from `tx` while reading into `rx`
+ */
+#define DRIVER_PAL_OP_TRANSFER 2
+
+/*
+ In place transfer operation, writing `len` bytes from `rx` and replacing them with read data
+ */
+#define DRIVER_PAL_OP_TRANSFER_IN_PLACE 3
+
+/*
+ Delay operation, delaying for `len` microseconds with chip select asserted
+ */
+#define DRIVER_PAL_OP_DELAY_US 4
+
+/*
+ Opaque HAL handle for C use, exported as `driver_pal_t`
+ */
+typedef struct driver_pal_t driver_pal_t;
+
+/*
+ SPI operation descriptor for `driver_pal_spi_transaction`, exported as `driver_pal_op_t`
+ */
+typedef struct driver_pal_op_t {
+  /*
+   Operation kind, one of `DRIVER_PAL_OP_*`
+   */
+  int op;
+  /*
+   Outgoing data for write and transfer operations
+   */
+  const uint8_t *tx;
+  /*
+   Incoming data for read and transfer operations
+   */
+  uint8_t *rx;
+  /*
+   Buffer length in bytes, or delay in microseconds for `DRIVER_PAL_OP_DELAY_US`
+   */
+  size_t len;
+} driver_pal_op_t;
+
+/*
+ Function pointer table for C drivers, exported as `driver_pal_vtable_t`.
+ Each function takes `ctx` as the first argument and returns a C return code.
+ */
+typedef struct driver_pal_vtable_t {
+  /*
+   Context passed to each function, the bound `driver_pal_t` handle
+   */
+  void *ctx;
+  /*
+   Transfer data in place
+   */
+  int (*spi_transfer)(void *ctx, uint8_t *data, size_t len);
+  /*
+   Write the prefix buffer then read into the data buffer
+   */
+  int (*spi_prefix_read)(void *ctx, const uint8_t *prefix, size_t prefix_len, uint8_t *data, size_t data_len);
+  /*
+   Write the prefix buffer then the data buffer
+   */
+  int (*spi_prefix_write)(void *ctx, const uint8_t *prefix, size_t prefix_len, const uint8_t *data, size_t data_len);
+  /*
+   Read a `DRIVER_PAL_PIN_*` input pin, returning 0 for low or 1 for high
+   */
+  int (*gpio_read)(void *ctx, int pin);
+  /*
+   Write a `DRIVER_PAL_PIN_*` output pin, low for 0 and high otherwise
+   */
+  int (*gpio_write)(void *ctx, int pin, int value);
+  /*
+   Delay for the provided number of milliseconds
+   */
+  int (*delay_ms)(void *ctx, uint32_t ms);
+} driver_pal_vtable_t;
+
+#if defined(DRIVER_PAL_HAL)
+/*
+ Create a handle from a device configuration file (see `hal::DeviceConfig`),
+ returning null on failure
+
+ # Safety
+ `config_file` must be a valid nul-terminated string
+ */
+driver_pal_t *driver_pal_create(const char *config_file);
+#endif
+
+/*
+ Destroy a handle, releasing the underlying HAL. Null and unknown handles are ignored.
+
+ # Safety
+ `hal` must not be in use by another thread
+ */
+void driver_pal_destroy(driver_pal_t *hal);
+
+/*
+ Fetch the code of the last error on a handle (0 if none), copying the
+ nul-terminated description into `buf` where provided, truncated to `len` bytes
+
+ # Safety
+ `hal` must be null or a handle, and `buf` null or valid for `len` bytes
+ */
+int driver_pal_last_error(driver_pal_t *hal, char *buf, size_t len);
+
+/*
+ Clear the last error on a handle
+
+ # Safety
+ `hal` must be null or a handle that has not been destroyed
+ */
+int driver_pal_clear_error(driver_pal_t *hal);
+
+/*
+ Fetch a static nul-terminated description of a return code
+ */
+const char *driver_pal_strerror(int code);
+
+/*
+ Transfer data in place, writing the buffer and replacing it with the read data
+
+ # Safety
+ `hal` must be null or a handle, and `data` null or valid for `len` bytes
+ */
+int driver_pal_spi_transfer(driver_pal_t *hal, uint8_t *data, uint16_t len);
+
+/*
+ Transfer data in place with a `size_t` length, see `driver_pal_spi_transfer`
+
+ # Safety
+ `hal` must be null or a handle, and `data` null or valid for `len` bytes
+ */
+int driver_pal_spi_transfer_sz(driver_pal_t *hal, uint8_t *data, size_t len);
+
+/*
+ Write the prefix buffer then read into the data buffer
+
+ # Safety
+ `hal` must be null or a handle, and buffers null or valid for the provided lengths
+ */
+int driver_pal_spi_prefix_read(driver_pal_t *hal, const uint8_t *prefix, uint16_t prefix_len, uint8_t *data, uint16_t data_len);
+
+/*
+ Prefixed read with `size_t` lengths, see `driver_pal_spi_prefix_read`
+
+ # Safety
+ `hal` must be null or a handle, and buffers null or valid for the provided lengths
+ */
+int driver_pal_spi_prefix_read_sz(driver_pal_t *hal, const uint8_t *prefix, size_t prefix_len, uint8_t *data, size_t data_len);
+
+/*
+ Write the prefix buffer then the data buffer
+
+ # Safety
+ `hal` must be null or a handle, and buffers null or valid for the provided lengths
+ */
+int driver_pal_spi_prefix_write(driver_pal_t *hal, const uint8_t *prefix, uint16_t prefix_len, const uint8_t *data, uint16_t data_len);
+
+/*
+ Prefixed write with `size_t` lengths, see `driver_pal_spi_prefix_write`
+
+ # Safety
+ `hal` must be null or a handle, and buffers null or valid for the provided lengths
+ */
+int driver_pal_spi_prefix_write_sz(driver_pal_t *hal, const uint8_t *prefix, size_t prefix_len, const uint8_t *data, size_t data_len);
+
+/*
+ Execute an array of operation descriptors as a single transaction,
+ with chip select asserted for the duration
+
+ # Safety
+ `hal` must be null or a handle, `ops` null or valid for `count` descriptors,
+ and descriptor buffers null or valid for the provided lengths
+ */
+int driver_pal_spi_transaction(driver_pal_t *hal, const driver_pal_op_t *ops, size_t count);
+
+/*
+ Fetch the busy pin state, returning 0 for low, 1 for high or a negative error
+
+ # Safety
+ `hal` must be null or a handle that has not been destroyed
+ */
+int driver_pal_get_busy(driver_pal_t *hal);
+
+/*
+ Fetch the ready pin state, returning 0 for low, 1 for high or a negative error
+
+ # Safety
+ `hal` must be null or a handle that has not been destroyed
+ */
+int driver_pal_get_ready(driver_pal_t *hal);
+
+/*
+ Set the reset pin state, low for 0 and high otherwise
+
+ # Safety
+ `hal` must be null or a handle that has not been destroyed
+ */
+int driver_pal_set_reset(driver_pal_t *hal, int state);
+
+/*
+ Delay for the provided number of microseconds
+
+ # Safety
+ `hal` must be null or a handle that has not been destroyed
+ */
+int driver_pal_delay_us(driver_pal_t *hal, uint32_t us);
+
+/*
+ Delay for the provided number of milliseconds
+
+ # Safety
+ `hal` must be null or a handle that has not been destroyed
+ */
+int driver_pal_delay_ms(driver_pal_t *hal, uint32_t ms);
+
+/*
+ Fill a function pointer table bound to the provided handle, for use with C drivers
+
+ # Safety
+ `hal` must be null or a handle, and `vtable` null or valid for writes.
+ The table is only valid until the handle is destroyed.
+ */
+int driver_pal_vtable(driver_pal_t *hal, driver_pal_vtable_t *vtable);
+
+#endif /* DRIVER_PAL_H */
@@ -0,0 +1,469 @@
+//! Command line utility for ad-hoc SPI and GPIO access using the `hal` backends
+//!
+//! ```text
+//! driver-pal --spi-dev /dev/spidev0.0 transfer 0x9f000000
+//! driver-pal --cp2130-dev 0 --format json prefix-read 80 4
+//! driver-pal --backend mock --ready-pin 5 wait-ready
+//! driver-pal --config device.toml run bringup.toml
+//! driver-pal --config device.toml reg --map radio.toml write STATUS.MODE standby
+//! ```
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+use driver_pal::hal::registers::{parse_int, Decoded};
+use driver_pal::hal::script::{format_hex, parse_hex};
+use driver_pal::hal::{
+    DeviceConfig, HalError, HalInputPin, HalInst, HalOutputPin, LogConfig, RegisterMap, Registers,
+    Script,
+};
+use driver_pal::{PinState, PrefixRead, PrefixWrite, Ready, Reset};
+use embedded_hal::delay::DelayUs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::spi::SpiDevice;
+
+#[derive(Debug, Parser)]
+#[clap(
+    name = "driver-pal",
+    about = "Ad-hoc SPI and GPIO access using driver-pal backends"
+)]
+struct Options {
+    #[clap(subcommand)]
+    command: Command,
+
+    /// Device configuration file (TOML, JSON or YAML),
+    /// explicitly provided device options override file values
+    #[clap(long, env = "DEVICE_CONFIG")]
+    config: Option<String>,
+
+    /// Output format for read data and pin levels
+    #[clap(long, value_enum, default_value = "hex")]
+    format: Format,
+
+    #[clap(flatten)]
+    device: DeviceConfig,
+
+    #[clap(flatten)]
+    log: LogConfig,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Transfer data, outputting the data read
+    Transfer {
+        /// Data to write, in hex
+        #[clap(value_parser = parse_hex)]
+        data: Bytes,
+    },
+    /// Write a prefix then read data, outputting the data read
+    PrefixRead {
+        /// Prefix to write, in hex
+        #[clap(value_parser = parse_hex)]
+        prefix: Bytes,
+        /// Number of bytes to read
+        len: usize,
+    },
+    /// Write data, with an optional prefix
+    Write {
+        /// Data to write, in hex
+        #[clap(value_parser = parse_hex)]
+        data: Bytes,
+        /// Prefix to write before the data, in hex
+        #[clap(long, value_parser = parse_hex)]
+        prefix: Option<Bytes>,
+    },
+    /// Read or write a named pin
+    Pin {
+        #[clap(subcommand)]
+        command: PinCommand,
+    },
+    /// Pulse the reset pin
+    Reset {
+        /// Reset pulse duration
+        #[clap(long, default_value = "10")]
+        pulse_ms: u32,
+        /// Delay after releasing reset
+        #[clap(long, default_value = "10")]
+        wait_ms: u32,
+    },
+    /// Wait for the ready pin to be asserted, failing on timeout
+    WaitReady {
+        /// Timeout waiting for the ready pin
+        #[clap(long, default_value = "1000")]
+        timeout_ms: u64,
+        /// Interval between pin reads
+        #[clap(long, default_value = "1")]
+        poll_ms: u64,
+    },
+    /// Run a transaction script (see `hal::script`), outputting the data read by each step
+    Run {
+        /// Script file (TOML, JSON or YAML)
+        script: String,
+    },
+    /// Read and write registers by name using a register map (see `hal::registers`)
+    Reg {
+        /// Register map file (TOML, JSON or YAML)
+        #[clap(long, env = "REGISTER_MAP")]
+        map: String,
+
+        #[clap(subcommand)]
+        command: RegCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum RegCommand {
+    /// Read and decode a register, or read a field by `REGISTER.FIELD` name
+    Read { name: String },
+    /// Write a register or field (`REGISTER.FIELD`) value, fields accept named values
+    Write { name: String, value: String },
+    /// Read and decode all readable registers
+    Dump,
+}
+
+#[derive(Debug, Subcommand)]
+enum PinCommand {
+    /// Read an input pin, outputting the level
+    Get {
+        #[clap(value_enum)]
+        name: InputName,
+    },
+    /// Set an output pin level
+    Set {
+        #[clap(value_enum)]
+        name: OutputName,
+        #[clap(value_enum)]
+        level: Level,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum InputName {
+    Busy,
+    Ready,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum OutputName {
+    Cs,
+    Reset,
+    Led0,
+    Led1,
+}
+
+/// Logical pin level
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Level {
+    #[value(alias = "1")]
+    High,
+    #[value(alias = "0")]
+    Low,
+}
+
+/// Output format
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    /// Hex encoded data, `high` / `low` for pin levels and decoded register fields
+    Hex,
+    /// Raw data, a single `0` / `1` byte for pin levels and big-endian register values
+    Bin,
+    /// JSON objects with `data` / `reads` / `registers` arrays, `value` or `level` fields
+    Json,
+}
+
+/// Command output
+#[derive(Debug, Clone, PartialEq)]
+enum Output {
+    Data(Vec<u8>),
+    Reads(Vec<Vec<u8>>),
+    Registers(Vec<Decoded>),
+    Value(u64),
+    Level(bool),
+    None,
+}
+
+/// Byte buffer alias, so clap parses hex data as a single value rather than a list
+type Bytes = Vec<u8>;
+
+fn main() {
+    let matches = Options::command().get_matches();
+
+    let o = match load_options(&matches) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("Error loading options: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    o.log.init();
+
+    let r = HalInst::load(&o.device)
+        .map_err(|e| e.into())
+        .and_then(|mut h| run(&mut h, &o.command));
+
+    let out = match r {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+
+            let mut source = e.source();
+            while let Some(s) = source {
+                eprintln!("  caused by: {}", s);
+                source = s.source();
+            }
+
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = write_output(&mut std::io::stdout(), o.format, &out) {
+        eprintln!("Error writing output: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Load options from parsed arguments, merging any configuration file
+fn load_options(matches: &ArgMatches) -> Result<Options, HalError> {
+    let mut o =
+        Options::from_arg_matches(matches).map_err(|e| HalError::InvalidConfig(e.to_string()))?;
+
+    if let Some(f) = &o.config {
+        o.device = DeviceConfig::load_with_overrides(f, matches)?;
+    }
+
+    Ok(o)
+}
+
+/// Execute a command against the provided HAL instance
+fn run(h: &mut HalInst, command: &Command) -> Result<Output, Box<dyn std::error::Error>> {
+    match command {
+        Command::Transfer { data } => {
+            let mut d = data.clone();
+            h.transfer_in_place(&mut d)?;
+            Ok(Output::Data(d))
+        }
+        Command::PrefixRead { prefix, len } => {
+            let mut d = vec![0u8; *len];
+            h.prefix_read(prefix, &mut d)?;
+            Ok(Output::Data(d))
+        }
+        Command::Write { data, prefix } => {
+            match prefix {
+                Some(p) => h.prefix_write(p, data)?,
+                None => h.write(data)?,
+            }
+            Ok(Output::None)
+        }
+        Command::Pin {
+            command: PinCommand::Get { name },
+        } => {
+            let p: &HalInputPin = match name {
+                InputName::Busy => &h.pins.busy,
+                InputName::Ready => &h.pins.ready,
+            };
+            Ok(Output::Level(p.is_high()?))
+        }
+        Command::Pin {
+            command: PinCommand::Set { name, level },
+        } => {
+            let p: &mut HalOutputPin = match name {
+                OutputName::Cs => &mut h.pins.cs,
+                OutputName::Reset => &mut h.pins.reset,
+                OutputName::Led0 => &mut h.pins.led0,
+                OutputName::Led1 => &mut h.pins.led1,
+            };
+            match level {
+                Level::High => p.set_high()?,
+                Level::Low => p.set_low()?,
+            }
+            Ok(Output::None)
+        }
+        Command::Reset { pulse_ms, wait_ms } => {
+            h.set_reset(PinState::Low)?;
+            h.delay_ms(*pulse_ms);
+            h.set_reset(PinState::High)?;
+            h.delay_ms(*wait_ms);
+            Ok(Output::None)
+        }
+        Command::WaitReady {
+            timeout_ms,
+            poll_ms,
+        } => {
+            let start = Instant::now();
+            let timeout = Duration::from_millis(*timeout_ms);
+
+            while h.get_ready()? != PinState::High {
+                if start.elapsed() > timeout {
+                    return Err(HalError::Timeout.into());
+                }
+                std::thread::sleep(Duration::from_millis(*poll_ms));
+            }
+
+            Ok(Output::None)
+        }
+        Command::Run { script } => {
+            let s = Script::load(script)?;
+            Ok(Output::Reads(s.run(h)?))
+        }
+        Command::Reg { map, command } => {
+            let mut r = Registers::new(RegisterMap::load(map)?, h)?;
+            run_reg(&mut r, command)
+        }
+    }
+}
+
+/// Execute a register command
+fn run_reg(
+    r: &mut Registers<&mut HalInst>,
+    command: &RegCommand,
+) -> Result<Output, Box<dyn std::error::Error>> {
+    match command {
+        RegCommand::Read { name } if name.contains('.') => Ok(Output::Value(r.read(name)?)),
+        RegCommand::Read { name } => Ok(Output::Registers(vec![r.read_decoded(name)?])),
+        RegCommand::Write { name, value } => {
+            let v = match r.map().lookup(name) {
+                Some((_, Some(f))) => f.parse_value(value),
+                _ => parse_int(value),
+            };
+            let v = v.map_err(|e| format!("Invalid value for {}: {}", name, e))?;
+
+            r.write(name, v)?;
+            Ok(Output::None)
+        }
+        RegCommand::Dump => Ok(Output::Registers(r.dump()?)),
+    }
+}
+
+/// Write command output in the selected format
+fn write_output<W: Write>(w: &mut W, format: Format, out: &Output) -> std::io::Result<()> {
+    match (format, out) {
+        (_, Output::None) => Ok(()),
+        (Format::Hex, Output::Data(d)) => writeln!(w, "{}", format_hex(d)),
+        (Format::Hex, Output::Reads(r)) => {
+            r.iter().try_for_each(|d| writeln!(w, "{}", format_hex(d)))
+        }
+        (Format::Hex, Output::Registers(r)) => r.iter().try_for_each(|d| writeln!(w, "{}", d)),
+        (Format::Hex, Output::Value(v)) => writeln!(w, "0x{:x}", v),
+        (Format::Hex, Output::Level(l)) => {
+            writeln!(w, "{}", if *l { "high" } else { "low" })
+        }
+        (Format::Bin, Output::Data(d)) => w.write_all(d),
+        (Format::Bin, Output::Reads(r)) => r.iter().try_for_each(|d| w.write_all(d)),
+        (Format::Bin, Output::Registers(r)) => r
+            .iter()
+            .try_for_each(|d| w.write_all(&d.value.to_be_bytes()[8 - d.width as usize / 8..])),
+        (Format::Bin, Output::Value(v)) => w.write_all(&v.to_be_bytes()),
+        (Format::Bin, Output::Level(l)) => w.write_all(&[*l as u8]),
+        (Format::Json, Output::Data(d)) => {
+            writeln!(w, "{}", serde_json::json!({ "data": d }))
+        }
+        (Format::Json, Output::Reads(r)) => {
+            writeln!(w, "{}", serde_json::json!({ "reads": r }))
+        }
+        (Format::Json, Output::Registers(r)) => {
+            writeln!(w, "{}", serde_json::json!({ "registers": r }))
+        }
+        (Format::Json, Output::Value(v)) => {
+            writeln!(w, "{}", serde_json::json!({ "value": v }))
+        }
+        (Format::Json, Output::Level(l)) => {
+            writeln!(w, "{}", serde_json::json!({ "level": l }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_mock_commands() {
+        let matches = Options::command()
+            .try_get_matches_from([
+                "driver-pal",
+                "--backend",
+                "mock",
+                "--ready-pin",
+                "5",
+                "transfer",
+                "0102",
+            ])
+            .unwrap();
+        let o = load_options(&matches).unwrap();
+        let mut h = HalInst::load(&o.device).unwrap();
+
+        let out = run(&mut h, &o.command).unwrap();
+        assert_eq!(out, Output::Data(vec![0x01, 0x02]));
+
+        let c = Command::WaitReady {
+            timeout_ms: 10,
+            poll_ms: 1,
+        };
+        assert_eq!(run(&mut h, &c).unwrap(), Output::None);
+
+        let c = Command::Pin {
+            command: PinCommand::Get {
+                name: InputName::Busy,
+            },
+        };
+        let e = run(&mut h, &c).unwrap_err();
+        assert!(e.downcast_ref::<HalError>().unwrap().is_no_pin());
+
+        let file = std::env::temp_dir().join("driver-pal-cli-test.toml");
+        std::fs::write(
+            &file,
+            "[[step]]\nprefix_read = { prefix = \"80\", len = 2 }\n",
+        )
+        .unwrap();
+
+        let c = Command::Run {
+            script: file.to_str().unwrap().to_string(),
+        };
+        assert_eq!(run(&mut h, &c).unwrap(), Output::Reads(vec![vec![0x00; 2]]));
+
+        std::fs::remove_file(file).unwrap();
+
+        let file = std::env::temp_dir().join("driver-pal-cli-regs.toml");
+        std::fs::write(&file, "[[register]]\nname = \"ID\"\naddress = 0x01\n").unwrap();
+
+        let c = Command::Reg {
+            map: file.to_str().unwrap().to_string(),
+            command: RegCommand::Dump,
+        };
+        match run(&mut h, &c).unwrap() {
+            Output::Registers(r) => assert_eq!((r[0].name.as_str(), r[0].value), ("ID", 0)),
+            o => panic!("unexpected output {:?}", o),
+        }
+
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_write_output() {
+        let out = |f, o| {
+            let mut b = Vec::new();
+            write_output(&mut b, f, &o).unwrap();
+            b
+        };
+
+        assert_eq!(out(Format::Hex, Output::Data(vec![0x0a, 0xff])), b"0aff\n");
+        assert_eq!(
+            out(Format::Bin, Output::Data(vec![0x0a, 0xff])),
+            [0x0a, 0xff]
+        );
+        assert_eq!(
+            out(Format::Json, Output::Data(vec![1, 2])),
+            b"{\"data\":[1,2]}\n"
+        );
+        assert_eq!(out(Format::Hex, Output::Level(true)), b"high\n");
+        assert_eq!(
+            out(Format::Json, Output::Level(false)),
+            b"{\"level\":false}\n"
+        );
+        assert_eq!(out(Format::Hex, Output::None), b"");
+    }
+}
@@ -1,125 +1,1297 @@
 //! Compatibility shims to allow C use of rust SPI peripherals
-//! This module provides mechanisms to convert an abstract `Wrapper` object to and from c void pointers,
-//! as well as C ffi compatible spi_read and spi_write functions using these context pointers.
+//! This module provides a C API over any `Hal` implementation via the opaque `driver_pal_t` handle
+//! (see `include/driver_pal.h`, generated with `cbindgen`).
+//!
+//! Handles are created in rust with `DriverPal::into_raw` (or from C with `driver_pal_create`
+//! when the `hal` feature is enabled) and released with `driver_pal_destroy`. All entry points
+//! check for null and unknown (never created or destroyed) handles against a registry of live
+//! handles as well as the handle type tag, and panics are caught rather than unwinding into C.
+//!
+//! Failures return one of the negative `DRIVER_PAL_ERR_*` codes, with the last error on each
+//! handle available via `driver_pal_last_error` and code descriptions via `driver_pal_strerror`.
+//!
+//! Buffer lengths are `uint16_t` for compatibility, with `_sz` variants taking `size_t` lengths
+//! for larger transfers. `driver_pal_spi_transaction` executes an array of `driver_pal_op_t`
+//! descriptors as a single transaction with chip select held throughout.
+//!
+//! For existing C drivers expecting a table of function pointers, `driver_pal_vtable` fills a
+//! `driver_pal_vtable_t` with SPI (using `size_t` lengths), GPIO and delay functions bound to
+//! a handle.
+
+use core::convert::TryFrom;
+use std::any::Any;
+use std::boxed::Box;
+use std::collections::HashSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::string::{String, ToString};
+use std::sync::{Mutex, OnceLock};
+use std::vec::Vec;
 
 use embedded_hal::delay::DelayUs;
-use embedded_hal::digital::OutputPin;
-use embedded_hal::spi::SpiDevice;
+use embedded_hal::spi::{Operation, SpiDevice};
+use libc::{c_char, c_int, c_void};
+
+use crate::{Busy, Error, PinState, PrefixRead, PrefixWrite, Ready, Reset};
+
+/// Success return code
+pub const DRIVER_PAL_OK: c_int = 0;
+
+/// Generic failure return code
+pub const DRIVER_PAL_ERR: c_int = -1;
+
+/// Null handle or buffer return code
+pub const DRIVER_PAL_ERR_NULL: c_int = -2;
+
+/// Invalid or mismatched handle return code
+pub const DRIVER_PAL_ERR_HANDLE: c_int = -3;
+
+/// Panic caught at the FFI boundary return code
+pub const DRIVER_PAL_ERR_PANIC: c_int = -4;
+
+/// SPI bus failure return code
+pub const DRIVER_PAL_ERR_SPI: c_int = -5;
+
+/// GPIO pin failure return code
+pub const DRIVER_PAL_ERR_PIN: c_int = -6;
+
+/// Operation aborted return code
+pub const DRIVER_PAL_ERR_ABORTED: c_int = -7;
+
+/// Operation timeout return code
+pub const DRIVER_PAL_ERR_TIMEOUT: c_int = -8;
+
+/// Pin not configured return code
+pub const DRIVER_PAL_ERR_NO_PIN: c_int = -9;
+
+/// No matching driver return code
+pub const DRIVER_PAL_ERR_NO_DRIVER: c_int = -10;
+
+/// Invalid configuration return code
+pub const DRIVER_PAL_ERR_CONFIG: c_int = -11;
+
+/// Operating system or USB I/O failure return code
+pub const DRIVER_PAL_ERR_IO: c_int = -12;
+
+/// Backend device failure return code
+pub const DRIVER_PAL_ERR_BACKEND: c_int = -13;
+
+/// Invalid argument return code
+pub const DRIVER_PAL_ERR_INVALID_ARG: c_int = -14;
+
+/// Reset pin identifier for `driver_pal_vtable_t` GPIO functions
+pub const DRIVER_PAL_PIN_RESET: c_int = 0;
+
+/// Busy pin identifier for `driver_pal_vtable_t` GPIO functions
+pub const DRIVER_PAL_PIN_BUSY: c_int = 1;
+
+/// Ready pin identifier for `driver_pal_vtable_t` GPIO functions
+pub const DRIVER_PAL_PIN_READY: c_int = 2;
 
-use crate::wrapper::Wrapper;
-use crate::{PrefixRead, PrefixWrite};
+/// Write operation, writing `len` bytes from `tx`
+pub const DRIVER_PAL_OP_WRITE: c_int = 0;
 
-/// Mark traits as cursed to provide a `Conv` implementation for FFI use
-pub trait Cursed {}
+/// Read operation, reading `len` bytes into `rx`
+pub const DRIVER_PAL_OP_READ: c_int = 1;
 
-/// Conv provides methods to convert rust types to and from c pointers
-pub trait Conv {
-    /// Generate a C void pointer that can later be re-cast into this object
-    fn to_c_ptr(&mut self) -> *mut libc::c_void;
-    /// Cast a C void pointer created by to_c_ptr back into this object
-    fn from_c_ptr<'a>(ctx: *mut libc::c_void) -> &'a mut Self;
+/// Transfer operation, writing `len` bytes from `tx` while reading into `rx`
+pub const DRIVER_PAL_OP_TRANSFER: c_int = 2;
+
+/// In place transfer operation, writing `len` bytes from `rx` and replacing them with read data
+pub const DRIVER_PAL_OP_TRANSFER_IN_PLACE: c_int = 3;
+
+/// Delay operation, delaying for `len` microseconds with chip select asserted
+pub const DRIVER_PAL_OP_DELAY_US: c_int = 4;
+
+/// Type tag for live `DriverPal` handles ("DPAL")
+const HANDLE_TAG: u32 = 0x4450_414c;
+
+/// Registry of live `DriverPal` handles, pointers from C are checked
+/// against this before being dereferenced
+fn handles() -> &'static Mutex<HashSet<usize>> {
+    static HANDLES: OnceLock<Mutex<HashSet<usize>>> = OnceLock::new();
+    HANDLES.get_or_init(Default::default)
+}
+
+/// Fetch a description of a C return code
+pub fn error_description(code: c_int) -> &'static str {
+    // Descriptions are nul-terminated for `driver_pal_strerror`
+    let s = error_description_c(code);
+    &s[..s.len() - 1]
 }
 
-impl<T> Conv for T
+fn error_description_c(code: c_int) -> &'static str {
+    match code {
+        DRIVER_PAL_OK => "Success\0",
+        DRIVER_PAL_ERR => "Unspecified error\0",
+        DRIVER_PAL_ERR_NULL => "Null handle or buffer\0",
+        DRIVER_PAL_ERR_HANDLE => "Invalid handle\0",
+        DRIVER_PAL_ERR_PANIC => "Panic in HAL operation\0",
+        DRIVER_PAL_ERR_SPI => "SPI error\0",
+        DRIVER_PAL_ERR_PIN => "Pin error\0",
+        DRIVER_PAL_ERR_ABORTED => "Operation aborted\0",
+        DRIVER_PAL_ERR_TIMEOUT => "Operation timed out\0",
+        DRIVER_PAL_ERR_NO_PIN => "Pin not configured\0",
+        DRIVER_PAL_ERR_NO_DRIVER => "No matching driver\0",
+        DRIVER_PAL_ERR_CONFIG => "Invalid configuration\0",
+        DRIVER_PAL_ERR_IO => "I/O error\0",
+        DRIVER_PAL_ERR_BACKEND => "Backend error\0",
+        DRIVER_PAL_ERR_INVALID_ARG => "Invalid argument\0",
+        _ => "Unknown error code\0",
+    }
+}
+
+/// Mapping of error types to C return codes, required for errors of `DynHal` implementors
+pub trait ErrorCode: core::fmt::Debug {
+    /// Fetch the negative C return code for this error
+    fn error_code(&self) -> c_int;
+
+    /// Fetch a description of this error
+    fn error_message(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl<SpiError, PinError> ErrorCode for Error<SpiError, PinError>
 where
-    T: Cursed,
+    SpiError: core::fmt::Debug,
+    PinError: core::fmt::Debug,
 {
-    /// Generate a C void pointer that can be re-cast into this object
-    fn to_c_ptr(&mut self) -> *mut libc::c_void {
-        self as *mut Self as *mut libc::c_void
+    fn error_code(&self) -> c_int {
+        match self {
+            Error::Spi(_) => DRIVER_PAL_ERR_SPI,
+            Error::Pin(_, _) => DRIVER_PAL_ERR_PIN,
+            Error::Aborted => DRIVER_PAL_ERR_ABORTED,
+            Error::Timeout => DRIVER_PAL_ERR_TIMEOUT,
+        }
     }
 
-    /// Cast a C void pointer created by to_c_ptr back into this object
-    fn from_c_ptr<'a>(ctx: *mut libc::c_void) -> &'a mut Self {
-        unsafe {
-            //assert!(ctx == ptr::null());
-            let s = ctx as *mut Self;
-            &mut *s
+    fn error_message(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ErrorCode for embedded_hal::spi::ErrorKind {
+    fn error_code(&self) -> c_int {
+        DRIVER_PAL_ERR_SPI
+    }
+}
+
+impl ErrorCode for embedded_hal::digital::ErrorKind {
+    fn error_code(&self) -> c_int {
+        DRIVER_PAL_ERR_PIN
+    }
+}
+
+#[cfg(feature = "hal")]
+impl ErrorCode for crate::hal::HalError {
+    fn error_code(&self) -> c_int {
+        use crate::hal::HalError::*;
+
+        match self.root() {
+            InvalidConfig(_) | InvalidSpiMode | Config(_) => DRIVER_PAL_ERR_CONFIG,
+            NoPin => DRIVER_PAL_ERR_NO_PIN,
+            NoDriver => DRIVER_PAL_ERR_NO_DRIVER,
+            Timeout => DRIVER_PAL_ERR_TIMEOUT,
+            #[cfg(any(feature = "hal-linux", feature = "hal-mcp2210"))]
+            Io(_) => DRIVER_PAL_ERR_IO,
+            #[cfg(feature = "hal-cp2130")]
+            Usb(_) => DRIVER_PAL_ERR_IO,
+            #[cfg(feature = "hal-ftdi")]
+            FtdiUsb(_) => DRIVER_PAL_ERR_IO,
+            #[cfg(feature = "hal-linux")]
+            Spi(_) => DRIVER_PAL_ERR_SPI,
+            #[cfg(feature = "hal-linux")]
+            Gpio(_) => DRIVER_PAL_ERR_PIN,
+            #[allow(unreachable_patterns)]
+            _ => DRIVER_PAL_ERR_BACKEND,
+        }
+    }
+
+    fn error_message(&self) -> String {
+        self.to_string()
+    }
+}
+
+#[cfg(feature = "mock")]
+impl ErrorCode for crate::mock::MockError {
+    fn error_code(&self) -> c_int {
+        DRIVER_PAL_ERR_SPI
+    }
+}
+
+#[cfg(feature = "mock")]
+impl ErrorCode for crate::mock::PinError {
+    fn error_code(&self) -> c_int {
+        DRIVER_PAL_ERR_PIN
+    }
+}
+
+/// Error from a `DynHal` operation with a description of the underlying error
+#[derive(Debug, Clone, PartialEq)]
+pub struct FfiError {
+    pub code: c_int,
+    pub message: String,
+}
+
+impl FfiError {
+    /// Create an error with the provided code and description
+    pub fn new<M: ToString>(code: c_int, message: M) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
         }
     }
+
+    /// Create an error from an underlying error
+    pub fn from_error<E: ErrorCode>(e: E) -> Self {
+        Self::new(e.error_code(), e.error_message())
+    }
+
+    fn from_panic(p: Box<dyn Any + Send>) -> Self {
+        let message = match (p.downcast_ref::<&str>(), p.downcast_ref::<String>()) {
+            (Some(s), _) => format!("panic in HAL operation: {}", s),
+            (_, Some(s)) => format!("panic in HAL operation: {}", s),
+            _ => "panic in HAL operation".to_string(),
+        };
+
+        Self::new(DRIVER_PAL_ERR_PANIC, message)
+    }
+}
+
+/// Object-safe HAL interface used by the C API, implemented for all `Hal` types
+/// with errors implementing `ErrorCode`
+pub trait DynHal {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), FfiError>;
+    fn transfer(&mut self, data: &mut [u8]) -> Result<(), FfiError>;
+    fn prefix_read(&mut self, prefix: &[u8], data: &mut [u8]) -> Result<(), FfiError>;
+    fn prefix_write(&mut self, prefix: &[u8], data: &[u8]) -> Result<(), FfiError>;
+    fn get_busy(&mut self) -> Result<PinState, FfiError>;
+    fn get_ready(&mut self) -> Result<PinState, FfiError>;
+    fn set_reset(&mut self, state: PinState) -> Result<(), FfiError>;
+    fn delay_us(&mut self, us: u32);
+    fn delay_ms(&mut self, ms: u32);
 }
 
-/// Mark Wrapper as a  c u r s e d  type to allow C coercion
-impl<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay> Cursed
-    for Wrapper<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay>
+impl<T> DynHal for T
+where
+    T: SpiDevice<u8> + Busy + Ready + Reset + DelayUs,
+    <T as embedded_hal::spi::ErrorType>::Error: ErrorCode,
+    <T as Busy>::Error: ErrorCode,
+    <T as Ready>::Error: ErrorCode,
+    <T as Reset>::Error: ErrorCode,
 {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), FfiError> {
+        SpiDevice::transaction(self, operations).map_err(FfiError::from_error)
+    }
+
+    fn transfer(&mut self, data: &mut [u8]) -> Result<(), FfiError> {
+        SpiDevice::transfer_in_place(self, data).map_err(FfiError::from_error)
+    }
+
+    fn prefix_read(&mut self, prefix: &[u8], data: &mut [u8]) -> Result<(), FfiError> {
+        PrefixRead::prefix_read(self, prefix, data).map_err(FfiError::from_error)
+    }
+
+    fn prefix_write(&mut self, prefix: &[u8], data: &[u8]) -> Result<(), FfiError> {
+        PrefixWrite::prefix_write(self, prefix, data).map_err(FfiError::from_error)
+    }
+
+    fn get_busy(&mut self) -> Result<PinState, FfiError> {
+        Busy::get_busy(self).map_err(FfiError::from_error)
+    }
+
+    fn get_ready(&mut self) -> Result<PinState, FfiError> {
+        Ready::get_ready(self).map_err(FfiError::from_error)
+    }
+
+    fn set_reset(&mut self, state: PinState) -> Result<(), FfiError> {
+        Reset::set_reset(self, state).map_err(FfiError::from_error)
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        DelayUs::delay_us(self, us)
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        DelayUs::delay_ms(self, ms)
+    }
+}
+
+/// Opaque HAL handle for C use, exported as `driver_pal_t`
+pub struct DriverPal {
+    tag: u32,
+    hal: Box<dyn DynHal>,
+    last_error: Option<FfiError>,
+}
+
+impl DriverPal {
+    /// Create a handle wrapping the provided HAL
+    pub fn new<H: DynHal + 'static>(hal: H) -> Self {
+        Self {
+            tag: HANDLE_TAG,
+            hal: Box::new(hal),
+            last_error: None,
+        }
+    }
+
+    /// Convert the handle into a raw pointer for C use,
+    /// this must be released with `driver_pal_destroy` or `DriverPal::from_raw`
+    pub fn into_raw(self) -> *mut DriverPal {
+        let p = Box::into_raw(Box::new(self));
+        handles().lock().unwrap().insert(p as usize);
+        p
+    }
+
+    /// Reclaim a handle created with `DriverPal::into_raw`
+    ///
+    /// # Safety
+    /// `p` must have been returned by `DriverPal::into_raw` and not previously reclaimed
+    pub unsafe fn from_raw(p: *mut DriverPal) -> Box<DriverPal> {
+        handles().lock().unwrap().remove(&(p as usize));
+        Box::from_raw(p)
+    }
+
+    /// Fetch the last error from an operation on this handle
+    pub fn last_error(&self) -> Option<&FfiError> {
+        self.last_error.as_ref()
+    }
+
+    /// Resolve a handle pointer, checking for null and unknown or destroyed handles
+    ///
+    /// # Safety
+    /// Live handles must not be accessed concurrently
+    unsafe fn from_ptr<'a>(p: *mut DriverPal) -> Result<&'a mut DriverPal, c_int> {
+        if p.is_null() {
+            error!("driver-pal ffi called with null handle");
+            return Err(DRIVER_PAL_ERR_NULL);
+        }
+
+        if !handles().lock().unwrap().contains(&(p as usize)) {
+            error!("driver-pal ffi called with invalid handle {:p}", p);
+            return Err(DRIVER_PAL_ERR_HANDLE);
+        }
+
+        let h = &mut *p;
+        if h.tag != HANDLE_TAG {
+            error!("driver-pal ffi called with mismatched handle {:p}", p);
+            return Err(DRIVER_PAL_ERR_HANDLE);
+        }
+
+        Ok(h)
+    }
+
+    /// Remove a handle from the registry and reclaim it,
+    /// returning `None` for unknown or destroyed handles
+    unsafe fn take(p: *mut DriverPal) -> Option<Box<DriverPal>> {
+        match handles().lock().unwrap().remove(&(p as usize)) {
+            true => Some(Box::from_raw(p)),
+            false => None,
+        }
+    }
 }
 
-impl<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay>
-    Wrapper<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay>
+impl Drop for DriverPal {
+    fn drop(&mut self) {
+        // Clear tag so stale pointers are detected where the memory is not yet reused
+        self.tag = 0;
+    }
+}
+
+/// Run an operation on a handle, checking the handle, catching panics
+/// and storing any error as the handle's last error
+unsafe fn with_hal<F>(p: *mut DriverPal, f: F) -> c_int
 where
-    Spi: SpiDevice<u8>,
-    CsPin: OutputPin,
-    Delay: DelayUs,
+    F: FnOnce(&mut dyn DynHal) -> Result<c_int, FfiError>,
 {
-    /// C FFI compatible spi_write function for dependency injection
-    pub extern "C" fn ffi_spi_write(
-        ctx: *mut libc::c_void,
-        prefix: *mut u8,
-        prefix_len: u16,
-        data: *mut u8,
-        data_len: u16,
-    ) -> isize {
-        // Coerce back into rust
-        let s = Self::from_c_ptr(ctx);
-
-        // Parse buffers
-        let prefix: &[u8] = unsafe { core::slice::from_raw_parts(prefix, prefix_len as usize) };
-        let data: &[u8] = unsafe { core::slice::from_raw_parts(data, data_len as usize) };
-
-        // Execute command and handle errors
-        match s.prefix_write(&prefix, &data) {
-            Ok(_) => 0,
-            Err(_e) => {
-                // TODO: removed this from wrapper
-                //s.err = Some(e);
-                -1
-            }
+    let h = match DriverPal::from_ptr(p) {
+        Ok(h) => h,
+        Err(e) => return e,
+    };
+
+    let r = match catch_unwind(AssertUnwindSafe(|| f(h.hal.as_mut()))) {
+        Ok(r) => r,
+        Err(p) => Err(FfiError::from_panic(p)),
+    };
+
+    match r {
+        Ok(v) => v,
+        Err(e) => {
+            error!("driver-pal ffi error: {}", e.message);
+
+            let code = e.code;
+            h.last_error = Some(e);
+            code
+        }
+    }
+}
+
+/// Build a slice from a C buffer, allowing null pointers for empty buffers
+unsafe fn c_slice<'a, T>(p: *const T, len: usize) -> Result<&'a [T], FfiError> {
+    match (len, p.is_null()) {
+        (0, _) => Ok(&[]),
+        (_, true) => Err(FfiError::new(DRIVER_PAL_ERR_NULL, "null buffer")),
+        _ => Ok(core::slice::from_raw_parts(p, len)),
+    }
+}
+
+/// Build a mutable slice from a C buffer, allowing null pointers for empty buffers
+unsafe fn c_slice_mut<'a, T>(p: *mut T, len: usize) -> Result<&'a mut [T], FfiError> {
+    match (len, p.is_null()) {
+        (0, _) => Ok(&mut []),
+        (_, true) => Err(FfiError::new(DRIVER_PAL_ERR_NULL, "null buffer")),
+        _ => Ok(core::slice::from_raw_parts_mut(p, len)),
+    }
+}
+
+/// Map a pin state to a C return value, 0 for low and 1 for high
+fn c_pin_state(s: PinState) -> c_int {
+    match s {
+        PinState::Low => 0,
+        PinState::High => 1,
+    }
+}
+
+/// Create a handle from a device configuration file (see `hal::DeviceConfig`),
+/// returning null on failure
+///
+/// # Safety
+/// `config_file` must be a valid nul-terminated string
+#[cfg(feature = "hal")]
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_create(config_file: *const c_char) -> *mut DriverPal {
+    if config_file.is_null() {
+        error!("driver-pal ffi called with null config file");
+        return core::ptr::null_mut();
+    }
+
+    let file = std::ffi::CStr::from_ptr(config_file).to_string_lossy();
+
+    let r = catch_unwind(|| {
+        let config: crate::hal::DeviceConfig = crate::hal::load_config(&file)?;
+        crate::hal::HalInst::load(&config)
+    });
+
+    match r {
+        Ok(Ok(h)) => DriverPal::new(h).into_raw(),
+        Ok(Err(e)) => {
+            error!("driver-pal ffi error loading {}: {}", file, e);
+            core::ptr::null_mut()
+        }
+        Err(_) => {
+            error!("driver-pal ffi caught panic");
+            core::ptr::null_mut()
+        }
+    }
+}
+
+/// Destroy a handle, releasing the underlying HAL. Null and unknown handles are ignored.
+///
+/// # Safety
+/// `hal` must not be in use by another thread
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_destroy(hal: *mut DriverPal) {
+    if hal.is_null() {
+        return;
+    }
+
+    let h = match DriverPal::take(hal) {
+        Some(h) => h,
+        None => {
+            error!("driver-pal ffi called with invalid handle {:p}", hal);
+            return;
         }
+    };
+
+    if catch_unwind(AssertUnwindSafe(|| drop(h))).is_err() {
+        error!("driver-pal ffi caught panic");
+    }
+}
+
+/// Fetch the code of the last error on a handle (0 if none), copying the
+/// nul-terminated description into `buf` where provided, truncated to `len` bytes
+///
+/// # Safety
+/// `hal` must be null or a handle, and `buf` null or valid for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_last_error(
+    hal: *mut DriverPal,
+    buf: *mut c_char,
+    len: usize,
+) -> c_int {
+    let h = match DriverPal::from_ptr(hal) {
+        Ok(h) => h,
+        Err(e) => return e,
+    };
+
+    let e = match &h.last_error {
+        Some(e) => e,
+        None => return DRIVER_PAL_OK,
+    };
+
+    if !buf.is_null() && len > 0 {
+        let n = e.message.len().min(len - 1);
+        core::ptr::copy_nonoverlapping(e.message.as_ptr() as *const c_char, buf, n);
+        *buf.add(n) = 0;
     }
 
-    /// C FFI compatible spi_read function for dependency injection
-    pub extern "C" fn ffi_spi_read(
-        ctx: *mut libc::c_void,
-        prefix: *mut u8,
-        prefix_len: u16,
-        data: *mut u8,
-        data_len: u16,
-    ) -> isize {
-        // Coerce back into rust
-        let s = Self::from_c_ptr(ctx);
-
-        // Parse buffers
-        let prefix: &[u8] = unsafe { core::slice::from_raw_parts(prefix, prefix_len as usize) };
-        let mut data: &mut [u8] =
-            unsafe { core::slice::from_raw_parts_mut(data, data_len as usize) };
-
-        // Execute command and handle errors
-        match s.prefix_read(&prefix, &mut data) {
-            Ok(_) => 0,
-            Err(_e) => {
-                // TODO: removed this from wrapper
-                //s.err = Some(e);
-                -1
+    e.code
+}
+
+/// Clear the last error on a handle
+///
+/// # Safety
+/// `hal` must be null or a handle that has not been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_clear_error(hal: *mut DriverPal) -> c_int {
+    match DriverPal::from_ptr(hal) {
+        Ok(h) => {
+            h.last_error = None;
+            DRIVER_PAL_OK
+        }
+        Err(e) => e,
+    }
+}
+
+/// Fetch a static nul-terminated description of a return code
+#[no_mangle]
+pub extern "C" fn driver_pal_strerror(code: c_int) -> *const c_char {
+    error_description_c(code).as_ptr() as *const c_char
+}
+
+/// Transfer data in place, writing the buffer and replacing it with the read data
+///
+/// # Safety
+/// `hal` must be null or a handle, and `data` null or valid for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_spi_transfer(
+    hal: *mut DriverPal,
+    data: *mut u8,
+    len: u16,
+) -> c_int {
+    driver_pal_spi_transfer_sz(hal, data, len as usize)
+}
+
+/// Transfer data in place with a `size_t` length, see `driver_pal_spi_transfer`
+///
+/// # Safety
+/// `hal` must be null or a handle, and `data` null or valid for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_spi_transfer_sz(
+    hal: *mut DriverPal,
+    data: *mut u8,
+    len: usize,
+) -> c_int {
+    with_hal(hal, |h| {
+        let data = c_slice_mut(data, len)?;
+
+        h.transfer(data).map(|_| DRIVER_PAL_OK)
+    })
+}
+
+/// Write the prefix buffer then read into the data buffer
+///
+/// # Safety
+/// `hal` must be null or a handle, and buffers null or valid for the provided lengths
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_spi_prefix_read(
+    hal: *mut DriverPal,
+    prefix: *const u8,
+    prefix_len: u16,
+    data: *mut u8,
+    data_len: u16,
+) -> c_int {
+    driver_pal_spi_prefix_read_sz(hal, prefix, prefix_len as usize, data, data_len as usize)
+}
+
+/// Prefixed read with `size_t` lengths, see `driver_pal_spi_prefix_read`
+///
+/// # Safety
+/// `hal` must be null or a handle, and buffers null or valid for the provided lengths
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_spi_prefix_read_sz(
+    hal: *mut DriverPal,
+    prefix: *const u8,
+    prefix_len: usize,
+    data: *mut u8,
+    data_len: usize,
+) -> c_int {
+    with_hal(hal, |h| {
+        let prefix = c_slice(prefix, prefix_len)?;
+        let data = c_slice_mut(data, data_len)?;
+
+        h.prefix_read(prefix, data).map(|_| DRIVER_PAL_OK)
+    })
+}
+
+/// Write the prefix buffer then the data buffer
+///
+/// # Safety
+/// `hal` must be null or a handle, and buffers null or valid for the provided lengths
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_spi_prefix_write(
+    hal: *mut DriverPal,
+    prefix: *const u8,
+    prefix_len: u16,
+    data: *const u8,
+    data_len: u16,
+) -> c_int {
+    driver_pal_spi_prefix_write_sz(hal, prefix, prefix_len as usize, data, data_len as usize)
+}
+
+/// Prefixed write with `size_t` lengths, see `driver_pal_spi_prefix_write`
+///
+/// # Safety
+/// `hal` must be null or a handle, and buffers null or valid for the provided lengths
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_spi_prefix_write_sz(
+    hal: *mut DriverPal,
+    prefix: *const u8,
+    prefix_len: usize,
+    data: *const u8,
+    data_len: usize,
+) -> c_int {
+    with_hal(hal, |h| {
+        let prefix = c_slice(prefix, prefix_len)?;
+        let data = c_slice(data, data_len)?;
+
+        h.prefix_write(prefix, data).map(|_| DRIVER_PAL_OK)
+    })
+}
+
+/// SPI operation descriptor for `driver_pal_spi_transaction`, exported as `driver_pal_op_t`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DriverPalOp {
+    /// Operation kind, one of `DRIVER_PAL_OP_*`
+    pub op: c_int,
+    /// Outgoing data for write and transfer operations
+    pub tx: *const u8,
+    /// Incoming data for read and transfer operations
+    pub rx: *mut u8,
+    /// Buffer length in bytes, or delay in microseconds for `DRIVER_PAL_OP_DELAY_US`
+    pub len: usize,
+}
+
+impl DriverPalOp {
+    /// Map a C operation descriptor to an SPI operation
+    ///
+    /// # Safety
+    /// Buffers must be null or valid for `len` bytes for the lifetime of the operation
+    unsafe fn operation<'a>(&self) -> Result<Operation<'a, u8>, FfiError> {
+        let o = match self.op {
+            DRIVER_PAL_OP_WRITE => Operation::Write(c_slice(self.tx, self.len)?),
+            DRIVER_PAL_OP_READ => Operation::Read(c_slice_mut(self.rx, self.len)?),
+            DRIVER_PAL_OP_TRANSFER => {
+                Operation::Transfer(c_slice_mut(self.rx, self.len)?, c_slice(self.tx, self.len)?)
+            }
+            DRIVER_PAL_OP_TRANSFER_IN_PLACE => {
+                Operation::TransferInPlace(c_slice_mut(self.rx, self.len)?)
+            }
+            DRIVER_PAL_OP_DELAY_US => match u32::try_from(self.len) {
+                Ok(us) => Operation::DelayUs(us),
+                Err(_) => {
+                    return Err(FfiError::new(
+                        DRIVER_PAL_ERR_INVALID_ARG,
+                        format!("delay of {} us out of range", self.len),
+                    ))
+                }
+            },
+            op => {
+                return Err(FfiError::new(
+                    DRIVER_PAL_ERR_INVALID_ARG,
+                    format!("invalid operation kind {}", op),
+                ))
             }
+        };
+
+        Ok(o)
+    }
+}
+
+/// Execute an array of operation descriptors as a single transaction,
+/// with chip select asserted for the duration
+///
+/// # Safety
+/// `hal` must be null or a handle, `ops` null or valid for `count` descriptors,
+/// and descriptor buffers null or valid for the provided lengths
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_spi_transaction(
+    hal: *mut DriverPal,
+    ops: *const DriverPalOp,
+    count: usize,
+) -> c_int {
+    with_hal(hal, |h| {
+        let mut operations = c_slice(ops, count)?
+            .iter()
+            .map(|o| o.operation())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        h.transaction(&mut operations).map(|_| DRIVER_PAL_OK)
+    })
+}
+
+/// Fetch the busy pin state, returning 0 for low, 1 for high or a negative error
+///
+/// # Safety
+/// `hal` must be null or a handle that has not been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_get_busy(hal: *mut DriverPal) -> c_int {
+    with_hal(hal, |h| h.get_busy().map(c_pin_state))
+}
+
+/// Fetch the ready pin state, returning 0 for low, 1 for high or a negative error
+///
+/// # Safety
+/// `hal` must be null or a handle that has not been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_get_ready(hal: *mut DriverPal) -> c_int {
+    with_hal(hal, |h| h.get_ready().map(c_pin_state))
+}
+
+/// Set the reset pin state, low for 0 and high otherwise
+///
+/// # Safety
+/// `hal` must be null or a handle that has not been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_set_reset(hal: *mut DriverPal, state: c_int) -> c_int {
+    let state = match state {
+        0 => PinState::Low,
+        _ => PinState::High,
+    };
+
+    with_hal(hal, |h| h.set_reset(state).map(|_| DRIVER_PAL_OK))
+}
+
+/// Delay for the provided number of microseconds
+///
+/// # Safety
+/// `hal` must be null or a handle that has not been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_delay_us(hal: *mut DriverPal, us: u32) -> c_int {
+    with_hal(hal, |h| {
+        h.delay_us(us);
+        Ok(DRIVER_PAL_OK)
+    })
+}
+
+/// Delay for the provided number of milliseconds
+///
+/// # Safety
+/// `hal` must be null or a handle that has not been destroyed
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_delay_ms(hal: *mut DriverPal, ms: u32) -> c_int {
+    with_hal(hal, |h| {
+        h.delay_ms(ms);
+        Ok(DRIVER_PAL_OK)
+    })
+}
+
+/// Function pointer table for C drivers, exported as `driver_pal_vtable_t`.
+/// Each function takes `ctx` as the first argument and returns a C return code.
+#[repr(C)]
+pub struct DriverPalVtable {
+    /// Context passed to each function, the bound `driver_pal_t` handle
+    pub ctx: *mut c_void,
+    /// Transfer data in place
+    pub spi_transfer:
+        Option<unsafe extern "C" fn(ctx: *mut c_void, data: *mut u8, len: usize) -> c_int>,
+    /// Write the prefix buffer then read into the data buffer
+    pub spi_prefix_read: Option<
+        unsafe extern "C" fn(
+            ctx: *mut c_void,
+            prefix: *const u8,
+            prefix_len: usize,
+            data: *mut u8,
+            data_len: usize,
+        ) -> c_int,
+    >,
+    /// Write the prefix buffer then the data buffer
+    pub spi_prefix_write: Option<
+        unsafe extern "C" fn(
+            ctx: *mut c_void,
+            prefix: *const u8,
+            prefix_len: usize,
+            data: *const u8,
+            data_len: usize,
+        ) -> c_int,
+    >,
+    /// Read a `DRIVER_PAL_PIN_*` input pin, returning 0 for low or 1 for high
+    pub gpio_read: Option<unsafe extern "C" fn(ctx: *mut c_void, pin: c_int) -> c_int>,
+    /// Write a `DRIVER_PAL_PIN_*` output pin, low for 0 and high otherwise
+    pub gpio_write:
+        Option<unsafe extern "C" fn(ctx: *mut c_void, pin: c_int, value: c_int) -> c_int>,
+    /// Delay for the provided number of milliseconds
+    pub delay_ms: Option<unsafe extern "C" fn(ctx: *mut c_void, ms: u32) -> c_int>,
+}
+
+impl DriverPalVtable {
+    /// Create a function table bound to a handle created with `DriverPal::into_raw`,
+    /// valid until the handle is destroyed
+    pub fn new(hal: *mut DriverPal) -> Self {
+        Self {
+            ctx: hal as *mut c_void,
+            spi_transfer: Some(vtable_spi_transfer),
+            spi_prefix_read: Some(vtable_spi_prefix_read),
+            spi_prefix_write: Some(vtable_spi_prefix_write),
+            gpio_read: Some(vtable_gpio_read),
+            gpio_write: Some(vtable_gpio_write),
+            delay_ms: Some(vtable_delay_ms),
         }
     }
 }
 
+unsafe extern "C" fn vtable_spi_transfer(ctx: *mut c_void, data: *mut u8, len: usize) -> c_int {
+    driver_pal_spi_transfer_sz(ctx as *mut DriverPal, data, len)
+}
+
+unsafe extern "C" fn vtable_spi_prefix_read(
+    ctx: *mut c_void,
+    prefix: *const u8,
+    prefix_len: usize,
+    data: *mut u8,
+    data_len: usize,
+) -> c_int {
+    driver_pal_spi_prefix_read_sz(ctx as *mut DriverPal, prefix, prefix_len, data, data_len)
+}
+
+unsafe extern "C" fn vtable_spi_prefix_write(
+    ctx: *mut c_void,
+    prefix: *const u8,
+    prefix_len: usize,
+    data: *const u8,
+    data_len: usize,
+) -> c_int {
+    driver_pal_spi_prefix_write_sz(ctx as *mut DriverPal, prefix, prefix_len, data, data_len)
+}
+
+unsafe extern "C" fn vtable_gpio_read(ctx: *mut c_void, pin: c_int) -> c_int {
+    match pin {
+        DRIVER_PAL_PIN_BUSY => driver_pal_get_busy(ctx as *mut DriverPal),
+        DRIVER_PAL_PIN_READY => driver_pal_get_ready(ctx as *mut DriverPal),
+        _ => {
+            error!("driver-pal ffi pin {} is not readable", pin);
+            DRIVER_PAL_ERR_NO_PIN
+        }
+    }
+}
+
+unsafe extern "C" fn vtable_gpio_write(ctx: *mut c_void, pin: c_int, value: c_int) -> c_int {
+    match pin {
+        DRIVER_PAL_PIN_RESET => driver_pal_set_reset(ctx as *mut DriverPal, value),
+        _ => {
+            error!("driver-pal ffi pin {} is not writable", pin);
+            DRIVER_PAL_ERR_NO_PIN
+        }
+    }
+}
+
+unsafe extern "C" fn vtable_delay_ms(ctx: *mut c_void, ms: u32) -> c_int {
+    driver_pal_delay_ms(ctx as *mut DriverPal, ms)
+}
+
+/// Fill a function pointer table bound to the provided handle, for use with C drivers
+///
+/// # Safety
+/// `hal` must be null or a handle, and `vtable` null or valid for writes.
+/// The table is only valid until the handle is destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn driver_pal_vtable(
+    hal: *mut DriverPal,
+    vtable: *mut DriverPalVtable,
+) -> c_int {
+    if let Err(e) = DriverPal::from_ptr(hal) {
+        return e;
+    }
+
+    if vtable.is_null() {
+        error!("driver-pal ffi called with null vtable");
+        return DRIVER_PAL_ERR_NULL;
+    }
+
+    vtable.write(DriverPalVtable::new(hal));
+
+    DRIVER_PAL_OK
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
-    #[derive(Debug, PartialEq)]
-    struct Something(bool);
-    impl Cursed for Something {}
+    #[cfg(feature = "mock")]
+    use crate::mock::{Mock, MockExec, MockTransaction};
 
+    /// Check the committed header matches the generated header,
+    /// set `DRIVER_PAL_UPDATE_HEADER` to regenerate it
     #[test]
-    fn test_compat() {
-        let mut s = Something(true);
-        let p = s.to_c_ptr();
-        let r = Something::from_c_ptr(p);
+    fn test_header() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/driver_pal.h"));
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/include/driver_pal.h");
+
+        if std::env::var_os("DRIVER_PAL_UPDATE_HEADER").is_some() {
+            std::fs::write(path, generated).unwrap();
+        }
+
+        let committed = std::fs::read_to_string(path).unwrap();
+        assert!(
+            committed == generated,
+            "include/driver_pal.h is out of date, regenerate with `DRIVER_PAL_UPDATE_HEADER=1 cargo test --features ffi test_header`"
+        );
+    }
+
+    #[cfg(feature = "ffi-test")]
+    extern "C" {
+        /// C API test program, see `tests/c/ffi_mock.c`
+        fn driver_pal_test_mock(hal: *mut DriverPal) -> c_int;
+    }
+
+    #[cfg(feature = "ffi-test")]
+    #[test]
+    fn test_c_api() {
+        let mut m = Mock::new();
+        let s = m.spi();
+
+        m.expect([
+            MockTransaction::spi_exec(
+                &s,
+                [
+                    MockExec::SpiWrite(vec![0x01]),
+                    MockExec::SpiWrite(vec![0xAA, 0xBB]),
+                ],
+            ),
+            MockTransaction::spi_exec(
+                &s,
+                [
+                    MockExec::SpiWrite(vec![0x02]),
+                    MockExec::SpiTransfer(vec![0; 2], vec![0; 2]),
+                ],
+            ),
+            MockTransaction::transfer(&s, vec![0x11, 0x22], vec![0x33, 0x44]),
+            MockTransaction::busy(&s, PinState::High),
+            MockTransaction::ready(&s, PinState::Low),
+            MockTransaction::reset(&s, PinState::High),
+            MockTransaction::DelayUs(100),
+        ]);
+
+        let h = DriverPal::new(s).into_raw();
+        let r = unsafe { driver_pal_test_mock(h) };
+        unsafe { driver_pal_destroy(h) };
+
+        assert_eq!(r, 0);
+        m.finalise();
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_handle_checks() {
+        let mut m = Mock::new();
+        let h = DriverPal::new(m.spi()).into_raw();
+
+        unsafe {
+            // Null handles and buffers
+            assert_eq!(
+                driver_pal_get_busy(core::ptr::null_mut()),
+                DRIVER_PAL_ERR_NULL
+            );
+            assert_eq!(
+                driver_pal_spi_transfer(h, core::ptr::null_mut(), 4),
+                DRIVER_PAL_ERR_NULL
+            );
+
+            // Mismatched handles
+            let mut not_a_handle = [0u64; 16];
+            assert_eq!(
+                driver_pal_get_busy(not_a_handle.as_mut_ptr() as *mut DriverPal),
+                DRIVER_PAL_ERR_HANDLE
+            );
+
+            // Panics are caught, the mock panics on unexpected transactions
+            let (prefix, mut data) = ([0x01u8], [0u8; 2]);
+            assert_eq!(
+                driver_pal_spi_prefix_read(h, prefix.as_ptr(), 1, data.as_mut_ptr(), 2),
+                DRIVER_PAL_ERR_PANIC
+            );
+
+            driver_pal_destroy(h);
+            driver_pal_destroy(core::ptr::null_mut());
+
+            // Destroyed handles are rejected without being dereferenced
+            assert_eq!(driver_pal_get_busy(h), DRIVER_PAL_ERR_HANDLE);
+            driver_pal_destroy(h);
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_last_error() {
+        let mut m = Mock::new();
+        let h = DriverPal::new(m.spi()).into_raw();
+        let mut buf = [0 as c_char; 8];
+
+        unsafe {
+            assert_eq!(
+                driver_pal_last_error(h, buf.as_mut_ptr(), buf.len()),
+                DRIVER_PAL_OK
+            );
+
+            driver_pal_spi_transfer(h, core::ptr::null_mut(), 4);
+            assert_eq!(
+                driver_pal_last_error(h, buf.as_mut_ptr(), buf.len()),
+                DRIVER_PAL_ERR_NULL
+            );
+
+            // Descriptions are truncated to the provided buffer
+            let message = std::ffi::CStr::from_ptr(buf.as_ptr());
+            assert_eq!(message.to_str().unwrap(), "null bu");
+
+            assert_eq!(driver_pal_clear_error(h), DRIVER_PAL_OK);
+            assert_eq!(
+                driver_pal_last_error(h, core::ptr::null_mut(), 0),
+                DRIVER_PAL_OK
+            );
+
+            driver_pal_destroy(h);
+        }
+    }
+
+    #[test]
+    fn test_error_codes() {
+        type E = Error<embedded_hal::spi::ErrorKind, embedded_hal::digital::ErrorKind>;
+
+        assert_eq!(
+            E::Spi(embedded_hal::spi::ErrorKind::Overrun).error_code(),
+            DRIVER_PAL_ERR_SPI
+        );
+        assert_eq!(E::Timeout.error_code(), DRIVER_PAL_ERR_TIMEOUT);
+
+        let s = unsafe { std::ffi::CStr::from_ptr(driver_pal_strerror(DRIVER_PAL_ERR_TIMEOUT)) };
+        assert_eq!(s.to_str().unwrap(), "Operation timed out");
+        assert_eq!(error_description(DRIVER_PAL_ERR_PIN), "Pin error");
+        assert_eq!(error_description(100), "Unknown error code");
+    }
+
+    #[cfg(feature = "hal")]
+    #[test]
+    fn test_hal_error_codes() {
+        use crate::hal::HalError;
+
+        assert_eq!(
+            HalError::NoPin.context("reading", "busy").error_code(),
+            DRIVER_PAL_ERR_NO_PIN
+        );
+        assert_eq!(
+            HalError::InvalidConfig("x".into()).error_code(),
+            DRIVER_PAL_ERR_CONFIG
+        );
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_vtable_large_buffers() {
+        let mut m = Mock::new();
+        let s = m.spi();
+
+        let data = vec![0xAB; 70_000];
+        m.expect([
+            MockTransaction::transfer(&s, data.clone(), data.clone()),
+            MockTransaction::spi_exec(
+                &s,
+                [
+                    MockExec::SpiWrite(vec![0x02]),
+                    MockExec::SpiWrite(data.clone()),
+                ],
+            ),
+        ]);
+
+        let h = DriverPal::new(s).into_raw();
+        let mut vt = core::mem::MaybeUninit::<DriverPalVtable>::uninit();
+        let (prefix, mut buff) = ([0x02], data.clone());
+
+        unsafe {
+            assert_eq!(driver_pal_vtable(h, vt.as_mut_ptr()), DRIVER_PAL_OK);
+            let vt = vt.assume_init();
+
+            let r = (vt.spi_transfer.unwrap())(vt.ctx, buff.as_mut_ptr(), buff.len());
+            assert_eq!(r, DRIVER_PAL_OK);
+
+            let r = (vt.spi_prefix_write.unwrap())(
+                vt.ctx,
+                prefix.as_ptr(),
+                1,
+                data.as_ptr(),
+                data.len(),
+            );
+            assert_eq!(r, DRIVER_PAL_OK);
+
+            driver_pal_destroy(h);
+        }
+
+        m.finalise();
+    }
+
+    #[cfg(feature = "ffi-test")]
+    extern "C" {
+        /// Example C driver init, see `tests/c/example_driver.c`
+        fn example_driver_init(hal: *const DriverPalVtable) -> c_int;
+    }
+
+    #[cfg(feature = "ffi-test")]
+    #[test]
+    fn test_c_driver() {
+        let mut m = Mock::new();
+        let s = m.spi();
+
+        m.expect([
+            MockTransaction::reset(&s, PinState::Low),
+            MockTransaction::delay_ms(1),
+            MockTransaction::reset(&s, PinState::High),
+            MockTransaction::busy(&s, PinState::High),
+            MockTransaction::delay_ms(1),
+            MockTransaction::busy(&s, PinState::Low),
+            MockTransaction::transfer(&s, vec![0x80, 0x00], vec![0x00, 0x5A]),
+        ]);
+
+        let h = DriverPal::new(s).into_raw();
+        let mut vt = core::mem::MaybeUninit::<DriverPalVtable>::uninit();
+
+        let r = unsafe {
+            assert_eq!(driver_pal_vtable(h, vt.as_mut_ptr()), DRIVER_PAL_OK);
+            example_driver_init(vt.as_ptr())
+        };
+
+        unsafe { driver_pal_destroy(h) };
+
+        assert_eq!(r, 0x5A);
+        m.finalise();
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_large_buffers() {
+        let mut m = Mock::new();
+        let s = m.spi();
+
+        let data = vec![0xAB; 70_000];
+        m.expect([MockTransaction::spi_exec(
+            &s,
+            [
+                MockExec::SpiWrite(vec![0x02]),
+                MockExec::SpiWrite(data.clone()),
+            ],
+        )]);
+
+        let h = DriverPal::new(s).into_raw();
+        let prefix = [0x02];
+
+        let r = unsafe {
+            driver_pal_spi_prefix_write_sz(h, prefix.as_ptr(), 1, data.as_ptr(), data.len())
+        };
+        assert_eq!(r, DRIVER_PAL_OK);
+
+        unsafe { driver_pal_destroy(h) };
+        m.finalise();
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_transaction() {
+        let mut m = Mock::new();
+        let s = m.spi();
+
+        m.expect([MockTransaction::spi_exec(
+            &s,
+            [
+                MockExec::SpiWrite(vec![0x03, 0x00]),
+                MockExec::SpiTransfer(vec![0x00; 4], vec![0x00; 4]),
+            ],
+        )]);
+
+        let h = DriverPal::new(s).into_raw();
+        let (cmd, mut resp) = ([0x03u8, 0x00], [0u8; 4]);
+
+        let ops = [
+            DriverPalOp {
+                op: DRIVER_PAL_OP_WRITE,
+                tx: cmd.as_ptr(),
+                rx: core::ptr::null_mut(),
+                len: cmd.len(),
+            },
+            DriverPalOp {
+                op: DRIVER_PAL_OP_TRANSFER_IN_PLACE,
+                tx: core::ptr::null(),
+                rx: resp.as_mut_ptr(),
+                len: resp.len(),
+            },
+        ];
+
+        unsafe {
+            assert_eq!(
+                driver_pal_spi_transaction(h, ops.as_ptr(), ops.len()),
+                DRIVER_PAL_OK
+            );
+
+            // Invalid descriptors are rejected before execution
+            let bad = [DriverPalOp { op: 42, ..ops[0] }];
+            assert_eq!(
+                driver_pal_spi_transaction(h, bad.as_ptr(), bad.len()),
+                DRIVER_PAL_ERR_INVALID_ARG
+            );
+
+            driver_pal_destroy(h);
+        }
+
+        m.finalise();
+    }
+
+    #[test]
+    fn test_transaction_read_transfer_delay() {
+        let mut m = Mock::new();
+        let s = m.spi();
+
+        m.expect([MockTransaction::spi_exec(
+            &s,
+            [
+                MockExec::SpiRead(vec![0x11, 0x22]),
+                MockExec::SpiTransfer(vec![0xAA, 0xBB, 0xCC], vec![0x33, 0x44, 0x55]),
+                MockExec::DelayUs(250),
+            ],
+        )]);
+
+        let h = DriverPal::new(s).into_raw();
+        let (mut read, tx, mut rx) = ([0u8; 2], [0xAAu8, 0xBB, 0xCC], [0u8; 3]);
+
+        let ops = [
+            DriverPalOp {
+                op: DRIVER_PAL_OP_READ,
+                tx: core::ptr::null(),
+                rx: read.as_mut_ptr(),
+                len: read.len(),
+            },
+            DriverPalOp {
+                op: DRIVER_PAL_OP_TRANSFER,
+                tx: tx.as_ptr(),
+                rx: rx.as_mut_ptr(),
+                len: rx.len(),
+            },
+            DriverPalOp {
+                op: DRIVER_PAL_OP_DELAY_US,
+                tx: core::ptr::null(),
+                rx: core::ptr::null_mut(),
+                len: 250,
+            },
+        ];
+
+        unsafe {
+            assert_eq!(
+                driver_pal_spi_transaction(h, ops.as_ptr(), ops.len()),
+                DRIVER_PAL_OK
+            );
+
+            driver_pal_destroy(h);
+        }
+
+        assert_eq!(read, [0x11, 0x22]);
+        assert_eq!(rx, [0x33, 0x44, 0x55]);
 
-        assert_eq!(&s, r);
+        m.finalise();
     }
 }
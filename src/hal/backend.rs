@@ -0,0 +1,249 @@
+//! Backend registry for HAL implementations
+//!
+//! Each bridge or platform implements `HalBackend` and is registered by name
+//! in a `Registry`, allowing third-party crates to provide additional bridge
+//! types without modifying `DeviceConfig` or `HalInst::load`.
+
+use std::boxed::Box;
+use std::str::FromStr;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use serde::Deserialize;
+
+use super::{DeviceConfig, HalError, HalInst, HalPins, PinConfig};
+
+/// HalBackend trait implemented by each HAL backend
+pub trait HalBackend: Send + Sync {
+    /// Name used to select this backend in configuration
+    fn name(&self) -> &'static str;
+
+    /// Check whether the provided configuration implicitly selects this backend,
+    /// used when no backend name is specified
+    fn detect(&self, _config: &DeviceConfig) -> bool {
+        false
+    }
+
+    /// Load a HAL instance using the provided configuration
+    fn load(&self, config: &DeviceConfig) -> Result<HalInst, HalError>;
+
+    /// Load an additional pin set for a device sharing the bus of an existing instance
+    /// (created by this backend), used for multiple chip selects on a single bus
+    fn load_pins(&self, _bus: &HalInst, _pins: &PinConfig) -> Result<HalPins, HalError> {
+        Err(HalError::InvalidConfig(format!(
+            "Backend '{}' does not support shared buses",
+            self.name()
+        )))
+    }
+}
+
+/// Registry of available HAL backends
+pub struct Registry {
+    backends: Vec<Box<dyn HalBackend>>,
+}
+
+impl Registry {
+    /// Create an empty registry with no backends
+    pub fn empty() -> Self {
+        Self {
+            backends: Vec::new(),
+        }
+    }
+
+    /// Register a backend, replacing any existing backend with the same name
+    pub fn register<B: HalBackend + 'static>(&mut self, backend: B) -> &mut Self {
+        self.backends.retain(|b| b.name() != backend.name());
+        self.backends.push(Box::new(backend));
+        self
+    }
+
+    /// Fetch a backend by name
+    pub fn get(&self, name: &str) -> Option<&dyn HalBackend> {
+        self.backends
+            .iter()
+            .find(|b| b.name() == name)
+            .map(|b| b.as_ref())
+    }
+
+    /// List the names of registered backends
+    pub fn names(&self) -> Vec<&'static str> {
+        self.backends.iter().map(|b| b.name()).collect()
+    }
+
+    /// Select the backend for the provided configuration, by name or by detection
+    pub fn select(&self, config: &DeviceConfig) -> Result<&dyn HalBackend, HalError> {
+        if let Some(name) = &config.backend {
+            return self.get(name).ok_or_else(|| {
+                HalError::InvalidConfig(format!(
+                    "Unknown backend '{}' (available: {:?})",
+                    name,
+                    self.names()
+                ))
+            });
+        }
+
+        let detected: Vec<_> = self.backends.iter().filter(|b| b.detect(config)).collect();
+
+        match detected.as_slice() {
+            [b] => Ok(b.as_ref()),
+            [] => Err(HalError::InvalidConfig(
+                "No SPI configuration provided or no matching implementation found".into(),
+            )),
+            _ => {
+                let names: Vec<_> = detected.iter().map(|b| b.name()).collect();
+                Err(HalError::InvalidConfig(format!(
+                    "Configuration matches multiple backends ({:?}), only one may be specified",
+                    names
+                )))
+            }
+        }
+    }
+
+    /// Load a hal instance using the backend selected by the provided configuration
+    pub fn load(&self, config: &DeviceConfig) -> Result<HalInst, HalError> {
+        let backend = self.select(config)?;
+
+        debug!("Creating {} hal driver", backend.name());
+
+        backend.load(config)
+    }
+}
+
+/// Default registry containing the backends enabled at compile time
+impl Default for Registry {
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut r = Self::empty();
+
+        #[cfg(all(feature = "hal-linux", target_os = "linux"))]
+        r.register(super::linux::LinuxDriver);
+
+        #[cfg(all(feature = "hal-linux", not(target_os = "linux")))]
+        r.register(UnsupportedLinuxDriver);
+
+        #[cfg(feature = "hal-cp2130")]
+        r.register(super::cp2130::Cp2130Driver);
+
+        #[cfg(feature = "hal-ftdi")]
+        r.register(super::ftdi::FtdiDriver);
+
+        #[cfg(feature = "hal-mcp2210")]
+        r.register(super::mcp2210::Mcp2210Driver);
+
+        #[cfg(feature = "mock")]
+        r.register(super::mock::MockDriver);
+
+        r
+    }
+}
+
+/// Placeholder for the linux backend on other platforms, reporting that
+/// the linux HAL is unavailable rather than an unknown backend
+#[cfg(all(feature = "hal-linux", not(target_os = "linux")))]
+struct UnsupportedLinuxDriver;
+
+#[cfg(all(feature = "hal-linux", not(target_os = "linux")))]
+impl HalBackend for UnsupportedLinuxDriver {
+    fn name(&self) -> &'static str {
+        "linux"
+    }
+
+    fn detect(&self, config: &DeviceConfig) -> bool {
+        config.spi_dev.is_some()
+    }
+
+    fn load(&self, _config: &DeviceConfig) -> Result<HalInst, HalError> {
+        Err(HalError::InvalidConfig(
+            "Linux HAL only supported on linux platforms".into(),
+        ))
+    }
+}
+
+/// Backend-specific option in `key=value` form
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct BackendOption {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for BackendOption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((k, v)) if !k.trim().is_empty() => Ok(Self {
+                key: k.trim().to_string(),
+                value: v.trim().to_string(),
+            }),
+            _ => Err(format!(
+                "invalid backend option '{}', expected key=value",
+                s
+            )),
+        }
+    }
+}
+
+impl std::convert::TryFrom<String> for BackendOption {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl DeviceConfig {
+    /// Fetch and parse a backend-specific option by key
+    pub fn option<T: FromStr>(&self, key: &str) -> Result<Option<T>, HalError> {
+        let o = match self.options.iter().rev().find(|o| o.key == key) {
+            Some(o) => o,
+            None => return Ok(None),
+        };
+
+        match o.value.parse() {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => Err(HalError::InvalidConfig(format!(
+                "Invalid value '{}' for backend option '{}'",
+                o.value, key
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct TestBackend;
+
+    impl HalBackend for TestBackend {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        fn load(&self, _config: &DeviceConfig) -> Result<HalInst, HalError> {
+            Err(HalError::NoDriver)
+        }
+    }
+
+    #[test]
+    fn test_backend_option() {
+        let o: BackendOption = "channel = 2".parse().unwrap();
+        assert_eq!(o.key, "channel");
+        assert_eq!(o.value, "2");
+
+        assert!("channel".parse::<BackendOption>().is_err());
+        assert!("=2".parse::<BackendOption>().is_err());
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut r = Registry::empty();
+        r.register(TestBackend);
+        r.register(TestBackend);
+
+        assert_eq!(r.names(), vec!["test"]);
+        assert!(r.get("test").is_some());
+        assert!(r.get("missing").is_none());
+    }
+}
@@ -0,0 +1,309 @@
+//! Configuration file loading
+//!
+//! Configuration files may be TOML (`.toml`), JSON (`.json`) or YAML (`.yaml` / `.yml`),
+//! selected by file extension. Parse errors report the line and column of the failure.
+
+use std::path::Path;
+use std::string::{String, ToString};
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, FromArgMatches};
+
+use super::{DeviceConfig, HalError};
+
+/// Supported configuration file formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Select a configuration format by file extension
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_lowercase();
+
+        match ext.as_str() {
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// Configuration loading error
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Error reading the configuration file
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    /// Unrecognised configuration file extension
+    UnsupportedFormat { path: String },
+
+    /// Error parsing the configuration file, with 1-based line and column where available
+    Parse {
+        path: String,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+}
+
+impl ConfigError {
+    /// Fetch the path of the configuration file that caused the error
+    pub fn path(&self) -> &str {
+        match self {
+            ConfigError::Io { path, .. } => path,
+            ConfigError::UnsupportedFormat { path } => path,
+            ConfigError::Parse { path, .. } => path,
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => write!(f, "Error reading {}: {}", path, source),
+            ConfigError::UnsupportedFormat { path } => write!(
+                f,
+                "Unsupported config format for {} (expected .toml, .json, .yaml or .yml)",
+                path
+            ),
+            ConfigError::Parse {
+                path,
+                line: Some(line),
+                column: Some(column),
+                message,
+            } => write!(f, "Error parsing {}:{}:{}: {}", path, line, column, message),
+            ConfigError::Parse { path, message, .. } => {
+                write!(f, "Error parsing {}: {}", path, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigError> for HalError {
+    fn from(e: ConfigError) -> Self {
+        HalError::Config(e)
+    }
+}
+
+/// Load a configuration file, selecting the format by file extension
+pub fn load_config<T>(file: &str) -> Result<T, ConfigError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let format = ConfigFormat::from_path(file).ok_or_else(|| ConfigError::UnsupportedFormat {
+        path: file.to_string(),
+    })?;
+
+    let d = std::fs::read_to_string(file).map_err(|e| ConfigError::Io {
+        path: file.to_string(),
+        source: e,
+    })?;
+
+    parse_config(file, &d, format)
+}
+
+/// Parse configuration data in the provided format, `path` is used for error reporting
+pub fn parse_config<T>(path: &str, data: &str, format: ConfigFormat) -> Result<T, ConfigError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let parse_error =
+        |line: Option<usize>, column: Option<usize>, message: String| ConfigError::Parse {
+            path: path.to_string(),
+            line,
+            column,
+            message,
+        };
+
+    match format {
+        ConfigFormat::Toml => toml::from_str(data).map_err(|e| {
+            // toml reports zero-based positions
+            let (line, column) = match e.line_col() {
+                Some((l, c)) => (Some(l + 1), Some(c + 1)),
+                None => (None, None),
+            };
+            parse_error(line, column, e.to_string())
+        }),
+        ConfigFormat::Json => serde_json::from_str(data)
+            .map_err(|e| parse_error(Some(e.line()), Some(e.column()), e.to_string())),
+        ConfigFormat::Yaml => serde_yaml::from_str(data).map_err(|e| {
+            let loc = e.location();
+            parse_error(
+                loc.as_ref().map(|l| l.line()),
+                loc.as_ref().map(|l| l.column()),
+                e.to_string(),
+            )
+        }),
+    }
+}
+
+/// Check whether an argument was explicitly provided on the command line or environment
+fn is_explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable)
+    )
+}
+
+/// Overwrite fields in `$dst` with those in `$src` where explicitly provided
+macro_rules! merge_explicit {
+    ($dst:expr, $src:expr, $matches:expr, $($field:ident),* $(,)?) => {
+        $(
+            if is_explicit($matches, stringify!($field)) {
+                $dst.$field = $src.$field;
+            }
+        )*
+    };
+}
+
+impl DeviceConfig {
+    /// Load a device configuration file, applying any CLI / environment overrides
+    /// from `matches` (as parsed for a command flattening `DeviceConfig`)
+    pub fn load_with_overrides(file: &str, matches: &ArgMatches) -> Result<Self, HalError> {
+        let mut config: DeviceConfig = load_config(file)?;
+
+        config.merge_args(matches)?;
+
+        Ok(config)
+    }
+
+    /// Merge CLI / environment arguments into this configuration,
+    /// fields are only overridden where explicitly provided so defaults do not mask file values
+    pub fn merge_args(&mut self, matches: &ArgMatches) -> Result<(), HalError> {
+        let args = DeviceConfig::from_arg_matches(matches)
+            .map_err(|e| HalError::InvalidConfig(e.to_string()))?;
+
+        merge_explicit!(
+            self,
+            args,
+            matches,
+            backend,
+            options,
+            spi_dev,
+            cp2130_dev,
+            cp2130_serial,
+            cp2130_path,
+            cp2130_id,
+            ftdi_dev,
+            mcp2210_dev,
+        );
+
+        merge_explicit!(self.spi, args.spi, matches, baud, mode);
+
+        merge_explicit!(
+            self.pins,
+            args.pins,
+            matches,
+            chip_select,
+            reset,
+            busy,
+            ready,
+            led0,
+            led1,
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::{CommandFactory, Parser};
+
+    use super::*;
+
+    #[test]
+    fn test_config_format() {
+        assert_eq!(ConfigFormat::from_path("a.toml"), Some(ConfigFormat::Toml));
+        assert_eq!(ConfigFormat::from_path("a.json"), Some(ConfigFormat::Json));
+        assert_eq!(ConfigFormat::from_path("a.YML"), Some(ConfigFormat::Yaml));
+        assert_eq!(ConfigFormat::from_path("a.ini"), None);
+        assert_eq!(ConfigFormat::from_path("a"), None);
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        let toml = "spi_dev = \"/dev/spidev0.0\"\nbaud = 2000000\nchip_select = 8\n";
+        let c: DeviceConfig = parse_config("a.toml", toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(c.spi_dev.as_deref(), Some("/dev/spidev0.0"));
+        assert_eq!(c.spi.baud, 2_000_000);
+        assert_eq!(c.spi.mode, 0);
+        assert_eq!(c.pins.chip_select.index, 8);
+
+        let json = r#"{ "spi_dev": "/dev/spidev0.1", "mode": 3 }"#;
+        let c: DeviceConfig = parse_config("a.json", json, ConfigFormat::Json).unwrap();
+        assert_eq!(c.spi_dev.as_deref(), Some("/dev/spidev0.1"));
+        assert_eq!(c.spi.mode, 3);
+
+        let yaml = "cp2130_dev: 1\nreset: 4\n";
+        let c: DeviceConfig = parse_config("a.yaml", yaml, ConfigFormat::Yaml).unwrap();
+        assert_eq!(c.cp2130_dev, Some(1));
+        assert_eq!(c.pins.reset.index, 4);
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let toml = "spi_dev = \"/dev/spidev0.0\"\nbaud = \"fast\"\n";
+        let e = parse_config::<DeviceConfig>("a.toml", toml, ConfigFormat::Toml).unwrap_err();
+        match e {
+            ConfigError::Parse { line, .. } => assert_eq!(line, Some(2)),
+            _ => panic!("unexpected error: {:?}", e),
+        }
+
+        let json = "{\n  \"spi_dev\": 4\n}";
+        let e = parse_config::<DeviceConfig>("a.json", json, ConfigFormat::Json).unwrap_err();
+        match e {
+            ConfigError::Parse { line, column, .. } => {
+                assert_eq!(line, Some(2));
+                assert!(column.is_some());
+            }
+            _ => panic!("unexpected error: {:?}", e),
+        }
+
+        let e = load_config::<DeviceConfig>("a.ini").unwrap_err();
+        assert!(matches!(e, ConfigError::UnsupportedFormat { .. }));
+    }
+
+    #[derive(Debug, Parser)]
+    struct Args {
+        #[clap(flatten)]
+        device: DeviceConfig,
+    }
+
+    #[test]
+    fn test_merge_args() {
+        let toml = "spi_dev = \"/dev/spidev0.0\"\nbaud = 2000000\nchip_select = 8\n";
+        let mut c: DeviceConfig = parse_config("a.toml", toml, ConfigFormat::Toml).unwrap();
+
+        let matches = Args::command()
+            .try_get_matches_from(["test", "--spi-mode", "1", "--busy-pin", "5"])
+            .unwrap();
+        c.merge_args(&matches).unwrap();
+
+        // Explicit arguments override file values
+        assert_eq!(c.spi.mode, 1);
+        assert_eq!(c.pins.busy.map(|p| p.index), Some(5));
+
+        // Defaulted arguments do not
+        assert_eq!(c.spi.baud, 2_000_000);
+        assert_eq!(c.pins.chip_select.index, 8);
+        assert_eq!(c.spi_dev.as_deref(), Some("/dev/spidev0.0"));
+    }
+}
@@ -1,92 +1,509 @@
 use std::convert::{TryFrom, TryInto};
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
 
 use driver_cp2130::prelude::*;
+use rusb::{Context, Device, DeviceDescriptor};
 
 use super::{
-    HalBase, HalError, HalInputPin, HalInst, HalOutputPin, HalPins, HalSpi, PinConfig, SpiConfig,
+    DeviceConfig, HalBackend, HalBase, HalError, HalInputPin, HalInst, HalOutputPin, HalPins,
+    HalSpi, PinBias, PinConfig, PinDrive, PinSpec, ResultExt, SpiConfig,
 };
 use crate::*;
 
+/// Supported CP2130 SPI clock rates
+const CLOCKS: &[usize] = &[
+    12_000_000, 6_000_000, 3_000_000, 1_500_000, 750_000, 375_000, 187_500, 93_750,
+];
+
 /// Convert a generic SPI config object into a CP2130 object
 impl TryFrom<super::SpiConfig> for driver_cp2130::SpiConfig {
     type Error = HalError;
 
     fn try_from(c: super::SpiConfig) -> Result<driver_cp2130::SpiConfig, Self::Error> {
+        let clock = SpiClock::try_from(c.baud as usize).map_err(|_| {
+            HalError::InvalidConfig(format!(
+                "Unsupported cp2130 baud rate: {} (supported: {:?})",
+                c.baud, CLOCKS
+            ))
+        })?;
+
+        // Map SPI mode to clock polarity and phase
+        let (spi_pol, spi_pha) = match c.mode {
+            0 => (SpiPol::IdleLow, SpiPha::SampleLeading),
+            1 => (SpiPol::IdleLow, SpiPha::SampleTrailing),
+            2 => (SpiPol::IdleHigh, SpiPha::SampleLeading),
+            3 => (SpiPol::IdleHigh, SpiPha::SampleTrailing),
+            _ => return Err(HalError::InvalidSpiMode),
+        };
+
         Ok(driver_cp2130::SpiConfig {
-            clock: SpiClock::try_from(c.baud as usize)?,
+            clock,
+            spi_pol,
+            spi_pha,
             ..driver_cp2130::SpiConfig::default()
         })
     }
 }
 
+/// CP2130 specific options, loaded from backend options
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cp2130Options {
+    /// SPI channel (hardware CS pin index, `channel=N`)
+    pub channel: u8,
+    /// Drive the channel CS pin push-pull rather than open-drain (`cs_mode=push-pull|open-drain`)
+    pub cs_push_pull: bool,
+}
+
+impl Default for Cp2130Options {
+    fn default() -> Self {
+        Self {
+            channel: 0,
+            cs_push_pull: true,
+        }
+    }
+}
+
+impl Cp2130Options {
+    /// Load CP2130 options from a device configuration
+    pub fn from_config(config: &DeviceConfig) -> Result<Self, HalError> {
+        let mut o = Self::default();
+
+        if let Some(c) = config.option::<u8>("channel")? {
+            if c > 10 {
+                return Err(HalError::InvalidConfig(format!(
+                    "Invalid cp2130 SPI channel: {} (range 0..10)",
+                    c
+                )));
+            }
+            o.channel = c;
+        }
+
+        if let Some(m) = config.option::<String>("cs_mode")? {
+            o.cs_push_pull = match m.as_str() {
+                "push-pull" => true,
+                "open-drain" => false,
+                _ => {
+                    return Err(HalError::InvalidConfig(format!(
+                        "Invalid cp2130 cs_mode: '{}' (expected push-pull or open-drain)",
+                        m
+                    )))
+                }
+            };
+        }
+
+        Ok(o)
+    }
+}
+
+/// CP2130 device selection, loaded from device configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cp2130Selector {
+    /// USB vendor ID
+    pub vid: u16,
+    /// USB product ID
+    pub pid: u16,
+    /// USB serial number
+    pub serial: Option<String>,
+    /// USB bus number and address
+    pub path: Option<(u8, u8)>,
+    /// Index within matching devices
+    pub index: usize,
+}
+
+impl Default for Cp2130Selector {
+    fn default() -> Self {
+        let f = Filter::default();
+
+        Self {
+            vid: f.vid,
+            pid: f.pid,
+            serial: None,
+            path: None,
+            index: 0,
+        }
+    }
+}
+
+impl Cp2130Selector {
+    /// Load a CP2130 selector from a device configuration
+    pub fn from_config(config: &DeviceConfig) -> Result<Self, HalError> {
+        let mut s = Self::default();
+
+        if let Some(i) = &config.cp2130_id {
+            let (vid, pid) = parse_pair(i, 16).ok_or_else(|| {
+                HalError::InvalidConfig(format!("Invalid cp2130 id '{}', expected VID:PID", i))
+            })?;
+            s.vid = vid;
+            s.pid = pid;
+        }
+
+        if let Some(p) = &config.cp2130_path {
+            let path = parse_pair(p, 10)
+                .and_then(|(b, a)| Some((u8::try_from(b).ok()?, u8::try_from(a).ok()?)));
+
+            s.path = match path {
+                Some(p) => Some(p),
+                None => {
+                    return Err(HalError::InvalidConfig(format!(
+                        "Invalid cp2130 path '{}', expected BUS:ADDRESS",
+                        p
+                    )))
+                }
+            };
+        }
+
+        s.serial = config.cp2130_serial.clone();
+        s.index = config.cp2130_dev.unwrap_or(0);
+
+        Ok(s)
+    }
+
+    /// Check whether a discovered device matches this selector
+    pub fn matches(&self, info: &Cp2130Info) -> bool {
+        if info.vid != self.vid || info.pid != self.pid {
+            return false;
+        }
+
+        if let Some((bus, address)) = self.path {
+            if info.bus != bus || info.address != address {
+                return false;
+            }
+        }
+
+        if let Some(serial) = &self.serial {
+            if info.serial.as_ref() != Some(serial) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Parse a `A:B` pair of integers with the provided radix
+fn parse_pair(s: &str, radix: u32) -> Option<(u16, u16)> {
+    let (a, b) = s.split_once(':')?;
+
+    match (u16::from_str_radix(a, radix), u16::from_str_radix(b, radix)) {
+        (Ok(a), Ok(b)) => Some((a, b)),
+        _ => None,
+    }
+}
+
+/// CP2130 device information
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cp2130Info {
+    pub bus: u8,
+    pub address: u8,
+    pub vid: u16,
+    pub pid: u16,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial: Option<String>,
+}
+
+/// Discovered CP2130 device
+pub struct Cp2130Device {
+    pub info: Cp2130Info,
+    pub device: Device<Context>,
+    pub descriptor: DeviceDescriptor,
+}
+
+/// List attached CP2130 devices matching the provided VID and PID
+pub fn list_devices(vid: u16, pid: u16) -> Result<Vec<Cp2130Device>, HalError> {
+    let devices = Manager::devices_filtered(Filter { vid, pid })?;
+
+    let mut found = Vec::with_capacity(devices.len());
+
+    for (device, descriptor) in devices {
+        let mut info = Cp2130Info {
+            bus: device.bus_number(),
+            address: device.address(),
+            vid: descriptor.vendor_id(),
+            pid: descriptor.product_id(),
+            manufacturer: None,
+            product: None,
+            serial: None,
+        };
+
+        // String descriptors require opening the device, which may fail on permissions
+        match device.open() {
+            Ok(h) => {
+                let timeout = Duration::from_millis(200);
+                let lang = h
+                    .read_languages(timeout)
+                    .ok()
+                    .and_then(|l| l.first().cloned());
+
+                if let Some(lang) = lang {
+                    info.manufacturer = h.read_manufacturer_string(lang, &descriptor, timeout).ok();
+                    info.product = h.read_product_string(lang, &descriptor, timeout).ok();
+                    info.serial = h.read_serial_number_string(lang, &descriptor, timeout).ok();
+                }
+            }
+            Err(e) => debug!(
+                "Unable to open cp2130 at {}:{} to read descriptors: {:?}",
+                info.bus, info.address, e
+            ),
+        }
+
+        found.push(Cp2130Device {
+            info,
+            device,
+            descriptor,
+        });
+    }
+
+    Ok(found)
+}
+
 /// CP2130 `Hal` implementation
 pub struct Cp2130Driver;
 
+/// CP2130 backend, selected by `backend = "cp2130"` or by `cp2130_dev`
+impl HalBackend for Cp2130Driver {
+    fn name(&self) -> &'static str {
+        "cp2130"
+    }
+
+    fn detect(&self, config: &DeviceConfig) -> bool {
+        config.cp2130_dev.is_some()
+            || config.cp2130_serial.is_some()
+            || config.cp2130_path.is_some()
+            || config.cp2130_id.is_some()
+    }
+
+    fn load(&self, config: &DeviceConfig) -> Result<HalInst, HalError> {
+        let selector = Cp2130Selector::from_config(config)?;
+        let opts = Cp2130Options::from_config(config)?;
+
+        Self::new(&selector, &config.spi, &config.pins, &opts)
+    }
+
+    fn load_pins(&self, bus: &HalInst, pins: &PinConfig) -> Result<HalPins, HalError> {
+        match &bus.base {
+            HalBase::Cp2130(cp2130) => Self::connect_pins(cp2130, pins),
+            _ => Err(HalError::InvalidConfig(
+                "Shared cp2130 bus requires a cp2130 instance".into(),
+            )),
+        }
+    }
+}
+
 impl Cp2130Driver {
     /// Load base CP2130 instance
     pub fn new(
-        index: usize,
+        selector: &Cp2130Selector,
         spi_config: &SpiConfig,
         pins: &PinConfig,
+        opts: &Cp2130Options,
     ) -> Result<HalInst, HalError> {
         // Fetch the matching device and descriptor
-        let (device, descriptor) = Manager::device(Filter::default(), index)?;
+        let d = list_devices(selector.vid, selector.pid)?
+            .into_iter()
+            .filter(|d| selector.matches(&d.info))
+            .nth(selector.index)
+            .ok_or_else(|| {
+                HalError::InvalidConfig(format!("No cp2130 device matching {:?}", selector))
+            })?;
+
+        debug!("Connecting to cp2130: {:?}", d.info);
+
+        let (device, descriptor) = (d.device, d.descriptor);
 
         // Create CP2130 object
-        let cp2130 = Cp2130::new(device, descriptor, UsbOptions::default())?;
+        let cp2130 = Cp2130::new(device, descriptor, UsbOptions::default()).context(
+            "opening cp2130",
+            format!("{}:{}", d.info.bus, d.info.address),
+        )?;
 
         // Connect SPI
-        let spi = cp2130.spi(0, spi_config.clone().try_into()?)?;
+        let mut config: driver_cp2130::SpiConfig = spi_config.clone().try_into()?;
+        config.cs_mode = match opts.cs_push_pull {
+            true => CsMode::PushPull,
+            false => CsMode::OpenDrain,
+        };
+
+        debug!(
+            "Connecting to cp2130 spi channel {} with config: {:?}",
+            opts.channel, config
+        );
+
+        let spi = cp2130.spi(opts.channel, config)?;
 
         // Connect pins
+        let pins = Self::connect_pins(&cp2130, pins)?;
+
+        // Return object
+        Ok(HalInst {
+            base: HalBase::Cp2130(cp2130),
+            spi: HalSpi::Cp2130(spi),
+            pins,
+        })
+    }
 
-        let chip_select =
-            cp2130.gpio_out(pins.chip_select as u8, GpioMode::PushPull, GpioLevel::High)?;
+    /// Connect pins on a CP2130 instance using the provided configuration
+    fn connect_pins(cp2130: &Cp2130, pins: &PinConfig) -> Result<HalPins, HalError> {
+        let chip_select = output_pin(cp2130, &pins.chip_select, PinState::High)?;
 
-        let reset = cp2130.gpio_out(pins.reset as u8, GpioMode::PushPull, GpioLevel::High)?;
+        let reset = output_pin(cp2130, &pins.reset, PinState::High)?;
 
-        let busy = match pins.busy {
-            Some(p) => HalInputPin::Cp2130(cp2130.gpio_in(p as u8)?),
+        let busy = match &pins.busy {
+            Some(p) => input_pin(cp2130, p)?,
             None => HalInputPin::None,
         };
 
-        let ready = match pins.ready {
-            Some(p) => HalInputPin::Cp2130(cp2130.gpio_in(p as u8)?),
+        let ready = match &pins.ready {
+            Some(p) => input_pin(cp2130, p)?,
             None => HalInputPin::None,
         };
 
-        let led0 = match pins.led0 {
-            Some(p) => HalOutputPin::Cp2130(cp2130.gpio_out(
-                p as u8,
-                GpioMode::PushPull,
-                GpioLevel::Low,
-            )?),
+        let led0 = match &pins.led0 {
+            Some(p) => output_pin(cp2130, p, PinState::Low)?,
             None => HalOutputPin::None,
         };
 
-        let led1 = match pins.led1 {
-            Some(p) => HalOutputPin::Cp2130(cp2130.gpio_out(
-                p as u8,
-                GpioMode::PushPull,
-                GpioLevel::Low,
-            )?),
+        let led1 = match &pins.led1 {
+            Some(p) => output_pin(cp2130, p, PinState::Low)?,
             None => HalOutputPin::None,
         };
 
-        let pins = HalPins {
-            cs: HalOutputPin::Cp2130(chip_select),
-            reset: HalOutputPin::Cp2130(reset),
+        Ok(HalPins {
+            cs: chip_select,
+            reset,
             busy,
             ready,
             led0,
             led1,
+        })
+    }
+}
+
+/// Check a pin bias setting is available in hardware
+///
+/// CP2130 GPIOs have fixed weak pull-ups (active on inputs and on open-drain outputs
+/// when released), so `pull-up` is accepted as-is while `pull-down` cannot be provided.
+fn check_bias(pin: &PinSpec) -> Result<(), HalError> {
+    match pin.bias {
+        None | Some(PinBias::PullUp) => Ok(()),
+        Some(b) => Err(HalError::InvalidConfig(format!(
+            "Pin {} bias {:?} is not supported by the cp2130 backend (fixed weak pull-up only)",
+            pin, b
+        ))),
+    }
+}
+
+/// Connect an output pin with the configured drive mode and initial (logical) level
+fn output_pin(cp2130: &Cp2130, pin: &PinSpec, default: PinState) -> Result<HalOutputPin, HalError> {
+    check_bias(pin)?;
+
+    let mode = match pin.drive {
+        Some(PinDrive::OpenDrain) => GpioMode::OpenDrain,
+        _ => GpioMode::PushPull,
+    };
+
+    let level = match pin.electrical(pin.initial.as_ref().unwrap_or(&default)) {
+        true => GpioLevel::High,
+        false => GpioLevel::Low,
+    };
+
+    debug!(
+        "Connecting to cp2130 output pin: {} (drive: {:?}, active low: {})",
+        pin, pin.drive, pin.active_low
+    );
+
+    let p = cp2130
+        .gpio_out(pin.index as u8, mode, level)
+        .context("configuring cp2130 pin", pin)?;
+
+    Ok(HalOutputPin::Cp2130(p).with_active_low(pin.active_low))
+}
+
+/// Connect an input pin, applying active-low inversion,
+/// drive settings do not apply to inputs
+fn input_pin(cp2130: &Cp2130, pin: &PinSpec) -> Result<HalInputPin, HalError> {
+    pin.check_support("cp2130", true, false)?;
+    check_bias(pin)?;
+
+    debug!("Connecting to cp2130 input pin: {}", pin);
+
+    let p = cp2130
+        .gpio_in(pin.index as u8)
+        .context("configuring cp2130 pin", pin)?;
+
+    Ok(HalInputPin::Cp2130(p).with_active_low(pin.active_low))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_spi_config() {
+        let c: driver_cp2130::SpiConfig = SpiConfig {
+            baud: 12_000_000,
+            mode: 3,
+        }
+        .try_into()
+        .unwrap();
+
+        assert!(matches!(c.spi_pol, SpiPol::IdleHigh));
+        assert!(matches!(c.spi_pha, SpiPha::SampleTrailing));
+
+        let r: Result<driver_cp2130::SpiConfig, _> = SpiConfig {
+            baud: 1_000_000,
+            mode: 0,
+        }
+        .try_into();
+        assert!(matches!(r, Err(HalError::InvalidConfig(_))));
+
+        let r: Result<driver_cp2130::SpiConfig, _> = SpiConfig {
+            baud: 12_000_000,
+            mode: 4,
+        }
+        .try_into();
+        assert!(matches!(r, Err(HalError::InvalidSpiMode)));
+    }
+
+    #[test]
+    fn test_selector() {
+        let info = Cp2130Info {
+            bus: 1,
+            address: 12,
+            vid: 0x10c4,
+            pid: 0x87a0,
+            manufacturer: None,
+            product: None,
+            serial: Some("ABC123".into()),
         };
 
-        // Return object
-        Ok(HalInst {
-            base: HalBase::Cp2130(cp2130),
-            spi: HalSpi::Cp2130(spi),
-            pins,
-        })
+        let mut s = Cp2130Selector::default();
+        s.vid = 0x10c4;
+        s.pid = 0x87a0;
+        assert!(s.matches(&info));
+
+        s.path = Some((1, 12));
+        s.serial = Some("ABC123".into());
+        assert!(s.matches(&info));
+
+        s.serial = Some("XYZ".into());
+        assert!(!s.matches(&info));
+
+        s.serial = None;
+        s.path = Some((2, 12));
+        assert!(!s.matches(&info));
+
+        assert_eq!(parse_pair("10c4:87a0", 16), Some((0x10c4, 0x87a0)));
+        assert_eq!(parse_pair("1:12", 10), Some((1, 12)));
+        assert_eq!(parse_pair("1", 10), None);
+    }
+
+    #[test]
+    fn test_check_bias() {
+        assert!(check_bias(&"1".parse().unwrap()).is_ok());
+        assert!(check_bias(&"1,pull-up".parse().unwrap()).is_ok());
+        assert!(check_bias(&"1,pull-down".parse().unwrap()).is_err());
     }
 }
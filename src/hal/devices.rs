@@ -0,0 +1,322 @@
+//! Multi-device configuration
+//!
+//! A `MultiDeviceConfig` describes a set of named devices, each with its own backend,
+//! SPI and pin configuration. Devices specifying the same `bus` share a single SPI
+//! instance, with the bus held for the duration of each transaction.
+//!
+//! ```toml
+//! [devices.radio]
+//! bus = "spi0"
+//! spi_dev = "/dev/spidev0.0"
+//! chip_select = 8
+//! reset = 17
+//!
+//! [devices.flash]
+//! bus = "spi0"
+//! spi_dev = "/dev/spidev0.0"
+//! chip_select = 7
+//! reset = 22
+//! ```
+
+use std::collections::BTreeMap;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use serde::Deserialize;
+
+use super::{DeviceConfig, HalBase, HalError, HalInst, Registry, ResultExt};
+
+/// Configuration for a set of named devices
+#[derive(Debug, Deserialize)]
+pub struct MultiDeviceConfig {
+    pub devices: BTreeMap<String, BusDeviceConfig>,
+}
+
+/// Device configuration with optional shared bus name
+#[derive(Debug, Deserialize)]
+pub struct BusDeviceConfig {
+    /// Shared bus name, devices with the same bus use a single SPI instance
+    #[serde(default)]
+    pub bus: Option<String>,
+
+    #[serde(flatten)]
+    pub device: DeviceConfig,
+}
+
+/// Set of HAL instances loaded from a `MultiDeviceConfig`
+pub struct HalDevices {
+    devices: BTreeMap<String, HalInst>,
+}
+
+impl HalDevices {
+    /// Load devices from the provided configuration using the default backends
+    pub fn load(config: &MultiDeviceConfig) -> Result<HalDevices, HalError> {
+        Self::load_with(&Registry::default(), config)
+    }
+
+    /// Load devices from the provided configuration using the provided backend registry
+    pub fn load_with(
+        registry: &Registry,
+        config: &MultiDeviceConfig,
+    ) -> Result<HalDevices, HalError> {
+        let mut devices = BTreeMap::new();
+
+        // Map of bus name to the first device (and config) loaded on that bus
+        let mut buses: BTreeMap<&str, (&str, &DeviceConfig)> = BTreeMap::new();
+
+        for (name, c) in &config.devices {
+            let bus = match &c.bus {
+                Some(b) => b.as_str(),
+                None => {
+                    let d =
+                        HalInst::load_with(registry, &c.device).context("loading device", name)?;
+                    devices.insert(name.clone(), d);
+                    continue;
+                }
+            };
+
+            let (owner, owner_config) = match buses.get(bus) {
+                Some(v) => *v,
+                None => {
+                    debug!("Loading device {} as owner of bus {}", name, bus);
+
+                    let d = HalInst::load_with(registry, &c.device)
+                        .context("loading device", name)?
+                        .into_shared();
+                    devices.insert(name.clone(), d);
+                    buses.insert(bus, (name, &c.device));
+                    continue;
+                }
+            };
+
+            debug!(
+                "Loading device {} on bus {} (shared with {})",
+                name, bus, owner
+            );
+
+            let backend = registry.select(&c.device)?;
+            if backend.name() != registry.select(owner_config)?.name() {
+                return Err(HalError::InvalidConfig(format!(
+                    "Device {} backend does not match device {} on bus {}",
+                    name, owner, bus
+                )));
+            }
+
+            if !same_selector(&c.device, owner_config) {
+                return Err(HalError::InvalidConfig(format!(
+                    "Device {} selector does not match device {} on bus {}",
+                    name, owner, bus
+                )));
+            }
+
+            if c.device.spi != owner_config.spi {
+                return Err(HalError::InvalidConfig(format!(
+                    "Device {} SPI configuration does not match device {} on bus {}",
+                    name, owner, bus
+                )));
+            }
+
+            let owner = &devices[owner];
+            let pins = backend
+                .load_pins(owner, &c.device.pins)
+                .context("loading device", name)?;
+
+            let d = HalInst {
+                base: HalBase::None,
+                spi: owner.shared_bus().unwrap(),
+                pins,
+            };
+            devices.insert(name.clone(), d);
+        }
+
+        Ok(HalDevices { devices })
+    }
+
+    /// Fetch a device by name
+    pub fn device(&mut self, name: &str) -> Option<&mut HalInst> {
+        self.devices.get_mut(name)
+    }
+
+    /// Take ownership of a device by name, removing it from the set
+    pub fn take(&mut self, name: &str) -> Option<HalInst> {
+        self.devices.remove(name)
+    }
+
+    /// List the names of loaded devices
+    pub fn names(&self) -> Vec<String> {
+        self.devices.keys().map(|k| k.to_string()).collect()
+    }
+}
+
+/// Check whether two configurations select the same underlying bus device
+fn same_selector(a: &DeviceConfig, b: &DeviceConfig) -> bool {
+    a.spi_dev == b.spi_dev
+        && a.cp2130_dev == b.cp2130_dev
+        && a.cp2130_serial == b.cp2130_serial
+        && a.cp2130_path == b.cp2130_path
+        && a.cp2130_id == b.cp2130_id
+        && a.ftdi_dev == b.ftdi_dev
+        && a.mcp2210_dev == b.mcp2210_dev
+}
+
+#[cfg(test)]
+mod test {
+    use std::boxed::Box;
+    use std::sync::{Arc, Mutex};
+    use std::vec;
+
+    use embedded_hal::spi::{Operation, SpiDevice};
+
+    use super::super::config::{parse_config, ConfigFormat};
+    use super::super::{HalBackend, HalInputPin, HalOutputPin, HalPins, HalSpi, PinConfig};
+    use super::*;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    /// SPI device logging transactions
+    struct LogSpi(Log);
+
+    impl SpiDevice<u8> for LogSpi {
+        fn transaction(&mut self, _ops: &mut [Operation<'_, u8>]) -> Result<(), HalError> {
+            self.0.lock().unwrap().push("spi".to_string());
+            Ok(())
+        }
+    }
+
+    impl embedded_hal::spi::ErrorType for LogSpi {
+        type Error = HalError;
+    }
+
+    /// Output pin logging set levels
+    struct LogPin(u64, Log);
+
+    impl embedded_hal::digital::OutputPin for LogPin {
+        fn set_high(&mut self) -> Result<(), HalError> {
+            self.1.lock().unwrap().push(format!("{}:high", self.0));
+            Ok(())
+        }
+
+        fn set_low(&mut self) -> Result<(), HalError> {
+            self.1.lock().unwrap().push(format!("{}:low", self.0));
+            Ok(())
+        }
+    }
+
+    impl embedded_hal::digital::ErrorType for LogPin {
+        type Error = HalError;
+    }
+
+    struct TestBackend(Log);
+
+    impl HalBackend for TestBackend {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        fn detect(&self, config: &DeviceConfig) -> bool {
+            config.spi_dev.is_some()
+        }
+
+        fn load(&self, config: &DeviceConfig) -> Result<HalInst, HalError> {
+            self.0.lock().unwrap().push("load".to_string());
+
+            Ok(HalInst {
+                base: HalBase::None,
+                spi: HalSpi::Dyn(Box::new(LogSpi(self.0.clone()))),
+                pins: log_pins(&config.pins, &self.0),
+            })
+        }
+
+        fn load_pins(&self, _bus: &HalInst, pins: &PinConfig) -> Result<HalPins, HalError> {
+            Ok(log_pins(pins, &self.0))
+        }
+    }
+
+    fn log_pins(pins: &PinConfig, log: &Log) -> HalPins {
+        HalPins {
+            cs: HalOutputPin::Dyn(Box::new(LogPin(pins.chip_select.index, log.clone()))),
+            reset: HalOutputPin::None,
+            busy: HalInputPin::None,
+            ready: HalInputPin::None,
+            led0: HalOutputPin::None,
+            led1: HalOutputPin::None,
+        }
+    }
+
+    #[test]
+    fn test_shared_bus() {
+        let log = Log::default();
+        let mut r = Registry::empty();
+        r.register(TestBackend(log.clone()));
+
+        let toml = r#"
+            [devices.radio]
+            bus = "spi0"
+            spi_dev = "spi0"
+            chip_select = 8
+
+            [devices.flash]
+            bus = "spi0"
+            spi_dev = "spi0"
+            chip_select = 7
+        "#;
+        let c: MultiDeviceConfig = parse_config("a.toml", toml, ConfigFormat::Toml).unwrap();
+
+        let mut d = HalDevices::load_with(&r, &c).unwrap();
+        assert_eq!(d.names(), vec!["flash", "radio"]);
+        assert_eq!(*log.lock().unwrap(), vec!["load"]);
+
+        d.device("radio").unwrap().write(&[0xAA]).unwrap();
+        d.device("flash").unwrap().write(&[0xBB]).unwrap();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["load", "8:low", "spi", "8:high", "7:low", "spi", "7:high"]
+        );
+
+        assert!(d.device("missing").is_none());
+    }
+
+    #[test]
+    fn test_mismatched_bus() {
+        let mut r = Registry::empty();
+        r.register(TestBackend(Log::default()));
+
+        let toml = r#"
+            [devices.radio]
+            bus = "spi0"
+            spi_dev = "spi0"
+            baud = 1000000
+
+            [devices.flash]
+            bus = "spi0"
+            spi_dev = "spi0"
+            baud = 2000000
+        "#;
+        let c: MultiDeviceConfig = parse_config("a.toml", toml, ConfigFormat::Toml).unwrap();
+
+        assert!(HalDevices::load_with(&r, &c).is_err());
+    }
+
+    #[test]
+    fn test_mismatched_selector() {
+        let mut r = Registry::empty();
+        r.register(TestBackend(Log::default()));
+
+        let toml = r#"
+            [devices.radio]
+            bus = "spi0"
+            spi_dev = "/dev/spidev0.0"
+
+            [devices.flash]
+            bus = "spi0"
+            spi_dev = "/dev/spidev1.0"
+        "#;
+        let c: MultiDeviceConfig = parse_config("a.toml", toml, ConfigFormat::Toml).unwrap();
+
+        assert!(matches!(
+            HalDevices::load_with(&r, &c),
+            Err(HalError::InvalidConfig(_))
+        ));
+    }
+}
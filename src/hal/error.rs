@@ -1,22 +1,51 @@
+use std::boxed::Box;
+use std::string::{String, ToString};
+
 /// Error type combining SPI and Pin errors for utility
 #[derive(Debug)]
 pub enum HalError {
-    InvalidConfig,
+    /// Invalid configuration, with a description of the problem
+    InvalidConfig(String),
     InvalidSpiMode,
     NoPin,
     NoDriver,
+    Timeout,
+
+    /// Error loading a configuration file
+    Config(super::ConfigError),
+
+    /// Error from an externally provided backend
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+
+    /// Error with the operation and device path or pin that caused it
+    Context {
+        op: &'static str,
+        target: String,
+        source: Box<HalError>,
+    },
 
     #[cfg(feature = "hal-cp2130")]
     Cp2130(driver_cp2130::Error),
 
-    #[cfg(feature = "hal-linux")]
-    Io(std::io::ErrorKind),
+    #[cfg(feature = "hal-cp2130")]
+    Usb(rusb::Error),
 
-    #[cfg(feature = "hal-linux")]
-    Sysfs(linux_embedded_hal::sysfs_gpio::Error),
+    #[cfg(feature = "hal-ftdi")]
+    Ftdi(ftdi_embedded_hal::Error<ftdi::Error>),
+
+    #[cfg(feature = "hal-ftdi")]
+    FtdiUsb(ftdi::Error),
+
+    /// MCP2210 command failure with status code
+    #[cfg(feature = "hal-mcp2210")]
+    Mcp2210(u8),
 
+    #[cfg(any(feature = "hal-linux", feature = "hal-mcp2210"))]
+    Io(std::io::Error),
+
+    /// GPIO character device error
     #[cfg(feature = "hal-linux")]
-    SysfsPin(linux_embedded_hal::SysfsPinError),
+    Gpio(gpio_cdev::errors::Error),
 
     #[cfg(feature = "hal-linux")]
     Spi(linux_embedded_hal::SPIError),
@@ -28,20 +57,57 @@ impl HalError {
         use HalError::*;
 
         match self {
-            InvalidConfig | InvalidSpiMode | NoPin => false,
+            InvalidConfig(_) | InvalidSpiMode | NoPin | NoDriver | Timeout => false,
             _ => true,
         }
     }
 
+    /// Check whether the HalError signals an operation timeout
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.root(), HalError::Timeout)
+    }
+
     /// Check whether the HalError signals no pin is available
     pub fn is_no_pin(&self) -> bool {
         use HalError::*;
 
-        match self {
+        match self.root() {
             NoPin => true,
             _ => false,
         }
     }
+
+    /// Fetch the underlying error, skipping any context
+    pub fn root(&self) -> &HalError {
+        match self {
+            HalError::Context { source, .. } => source.root(),
+            e => e,
+        }
+    }
+
+    /// Attach the failed operation and device path or pin to an error
+    pub fn context<T: ToString>(self, op: &'static str, target: T) -> Self {
+        HalError::Context {
+            op,
+            target: target.to_string(),
+            source: Box::new(self),
+        }
+    }
+}
+
+/// Extension trait to attach context to fallible HAL operations
+pub trait ResultExt<T> {
+    /// Attach the failed operation and device path or pin to an error
+    fn context<S: ToString>(self, op: &'static str, target: S) -> Result<T, HalError>;
+}
+
+impl<T, E> ResultExt<T> for Result<T, E>
+where
+    E: Into<HalError>,
+{
+    fn context<S: ToString>(self, op: &'static str, target: S) -> Result<T, HalError> {
+        self.map_err(|e| e.into().context(op, target))
+    }
 }
 
 #[cfg(feature = "hal-cp2130")]
@@ -51,24 +117,38 @@ impl From<driver_cp2130::Error> for HalError {
     }
 }
 
-#[cfg(feature = "hal-linux")]
-impl From<std::io::Error> for HalError {
-    fn from(e: std::io::Error) -> Self {
-        Self::Io(e.kind())
+#[cfg(feature = "hal-cp2130")]
+impl From<rusb::Error> for HalError {
+    fn from(e: rusb::Error) -> Self {
+        Self::Usb(e)
     }
 }
 
-#[cfg(feature = "hal-linux")]
-impl From<linux_embedded_hal::SysfsPinError> for HalError {
-    fn from(e: linux_embedded_hal::SysfsPinError) -> Self {
-        Self::SysfsPin(e)
+#[cfg(feature = "hal-ftdi")]
+impl From<ftdi_embedded_hal::Error<ftdi::Error>> for HalError {
+    fn from(e: ftdi_embedded_hal::Error<ftdi::Error>) -> Self {
+        Self::Ftdi(e)
+    }
+}
+
+#[cfg(feature = "hal-ftdi")]
+impl From<ftdi::Error> for HalError {
+    fn from(e: ftdi::Error) -> Self {
+        Self::FtdiUsb(e)
+    }
+}
+
+#[cfg(any(feature = "hal-linux", feature = "hal-mcp2210"))]
+impl From<std::io::Error> for HalError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
     }
 }
 
 #[cfg(feature = "hal-linux")]
-impl From<linux_embedded_hal::sysfs_gpio::Error> for HalError {
-    fn from(e: linux_embedded_hal::sysfs_gpio::Error) -> Self {
-        Self::Sysfs(e)
+impl From<gpio_cdev::errors::Error> for HalError {
+    fn from(e: gpio_cdev::errors::Error) -> Self {
+        Self::Gpio(e)
     }
 }
 
@@ -79,14 +159,124 @@ impl From<linux_embedded_hal::SPIError> for HalError {
     }
 }
 
+/// SPI error kinds are propagated from underlying SPI errors where available
 impl embedded_hal::spi::Error for HalError {
     fn kind(&self) -> embedded_hal::spi::ErrorKind {
-        embedded_hal::spi::ErrorKind::Other
+        use embedded_hal::spi::ErrorKind;
+
+        match self {
+            HalError::Context { source, .. } => embedded_hal::spi::Error::kind(source.as_ref()),
+            // Pins are only used by SPI operations to assert and deassert chip select
+            HalError::NoPin => ErrorKind::ChipSelectFault,
+            #[cfg(feature = "hal-linux")]
+            HalError::Gpio(_) => ErrorKind::ChipSelectFault,
+            #[cfg(feature = "hal-linux")]
+            HalError::Spi(e) => embedded_hal::spi::Error::kind(e),
+            #[cfg(feature = "hal-cp2130")]
+            HalError::Cp2130(e) => embedded_hal::spi::Error::kind(e),
+            #[cfg(feature = "hal-ftdi")]
+            HalError::Ftdi(e) => embedded_hal::spi::Error::kind(e),
+            // Other status codes reject malformed commands rather than signalling bus faults
+            #[cfg(feature = "hal-mcp2210")]
+            HalError::Mcp2210(super::mcp2210::STATUS_BUS_UNAVAILABLE) => ErrorKind::ModeFault,
+            // IO errors are device or transport (hidraw, poll) failures with no SPI
+            // equivalent, and embedded-hal has no timeout kind (see `is_timeout`)
+            _ => ErrorKind::Other,
+        }
     }
 }
 
+/// Digital error kinds are propagated from underlying pin errors where available,
+/// `digital::ErrorKind` only defines `Other` so no other variants can be classified
 impl embedded_hal::digital::Error for HalError {
     fn kind(&self) -> embedded_hal::digital::ErrorKind {
-        embedded_hal::digital::ErrorKind::Other
+        match self {
+            HalError::Context { source, .. } => embedded_hal::digital::Error::kind(source.as_ref()),
+            #[cfg(feature = "hal-cp2130")]
+            HalError::Cp2130(e) => embedded_hal::digital::Error::kind(e),
+            #[cfg(feature = "hal-ftdi")]
+            HalError::Ftdi(e) => embedded_hal::digital::Error::kind(e),
+            _ => embedded_hal::digital::ErrorKind::Other,
+        }
+    }
+}
+
+impl std::fmt::Display for HalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use HalError::*;
+
+        match self {
+            InvalidConfig(reason) => write!(f, "Invalid configuration: {}", reason),
+            InvalidSpiMode => write!(f, "Invalid SPI mode"),
+            NoPin => write!(f, "No pin configured"),
+            NoDriver => write!(f, "No driver available"),
+            Timeout => write!(f, "Operation timed out"),
+            Config(e) => write!(f, "{}", e),
+            Backend(e) => write!(f, "Backend error: {}", e),
+            Context { op, target, .. } => write!(f, "Error {} {}", op, target),
+            #[cfg(feature = "hal-cp2130")]
+            Cp2130(e) => write!(f, "CP2130 error: {:?}", e),
+            #[cfg(feature = "hal-cp2130")]
+            Usb(e) => write!(f, "USB error: {}", e),
+            #[cfg(feature = "hal-ftdi")]
+            Ftdi(e) => write!(f, "FTDI error: {:?}", e),
+            #[cfg(feature = "hal-ftdi")]
+            FtdiUsb(e) => write!(f, "FTDI USB error: {:?}", e),
+            #[cfg(feature = "hal-mcp2210")]
+            Mcp2210(s) => write!(f, "MCP2210 command failed with status 0x{:02x}", s),
+            #[cfg(any(feature = "hal-linux", feature = "hal-mcp2210"))]
+            Io(e) => write!(f, "IO error: {}", e),
+            #[cfg(feature = "hal-linux")]
+            Gpio(e) => write!(f, "GPIO error: {}", e),
+            #[cfg(feature = "hal-linux")]
+            Spi(e) => write!(f, "SPI error: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for HalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use HalError::*;
+
+        match self {
+            Config(e) => Some(e),
+            Backend(e) => Some(e.as_ref()),
+            Context { source, .. } => Some(source.as_ref()),
+            #[cfg(feature = "hal-cp2130")]
+            Usb(e) => Some(e),
+            #[cfg(any(feature = "hal-linux", feature = "hal-mcp2210"))]
+            Io(e) => Some(e),
+            #[cfg(feature = "hal-linux")]
+            Gpio(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use super::*;
+
+    #[test]
+    fn test_context() {
+        let e: Result<(), HalError> = Err(HalError::NoPin);
+        let e = e.context("exporting pin", 17).unwrap_err();
+
+        assert!(e.is_no_pin());
+        assert_eq!(e.to_string(), "Error exporting pin 17");
+        assert_eq!(e.source().unwrap().to_string(), "No pin configured");
+    }
+
+    #[test]
+    fn test_spi_kind() {
+        use embedded_hal::spi::{Error, ErrorKind};
+
+        let e = HalError::NoPin.context("asserting chip select", 8);
+        assert_eq!(e.kind(), ErrorKind::ChipSelectFault);
+
+        assert_eq!(HalError::Timeout.kind(), ErrorKind::Other);
+        assert_eq!(HalError::InvalidSpiMode.kind(), ErrorKind::Other);
     }
 }
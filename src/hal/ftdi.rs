@@ -0,0 +1,235 @@
+use embedded_hal::delay::DelayUs;
+use embedded_hal::spi::{Operation, Polarity, SpiBus};
+
+use ftdi_embedded_hal as ftdi_hal;
+
+use super::{
+    DeviceConfig, HalBackend, HalBase, HalDelay, HalError, HalInputPin, HalInst, HalOutputPin,
+    HalPins, HalSpi, PinConfig, PinSpec, SpiConfig,
+};
+use crate::PinState;
+
+/// Default FT232H USB vendor ID
+pub const FTDI_VID: u16 = 0x0403;
+
+/// Default FT232H USB product ID
+pub const FTDI_PID: u16 = 0x6014;
+
+/// FTDI device type
+pub type Device = ftdi::Device;
+
+/// FTDI SPI device, wraps the MPSSE SPI bus with chip select managed via GPIO
+pub struct FtdiSpi(ftdi_hal::Spi<Device>);
+
+impl embedded_hal::spi::SpiDevice<u8> for FtdiSpi {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        for o in operations {
+            match o {
+                Operation::Read(d) => self.0.read(d)?,
+                Operation::Write(d) => self.0.write(d)?,
+                Operation::Transfer(r, w) => self.0.transfer(r, w)?,
+                Operation::TransferInPlace(d) => self.0.transfer_in_place(d)?,
+                Operation::DelayUs(us) => {
+                    self.0.flush()?;
+                    HalDelay::new().delay_us(*us);
+                }
+            }
+        }
+
+        self.0.flush()?;
+
+        Ok(())
+    }
+}
+
+impl embedded_hal::spi::ErrorType for FtdiSpi {
+    type Error = HalError;
+}
+
+/// FTDI FT232H MPSSE `Hal` implementation
+pub struct FtdiDriver;
+
+/// FTDI backend, selected by `backend = "ftdi"` or by `ftdi_dev`
+impl HalBackend for FtdiDriver {
+    fn name(&self) -> &'static str {
+        "ftdi"
+    }
+
+    fn detect(&self, config: &DeviceConfig) -> bool {
+        config.ftdi_dev.is_some()
+    }
+
+    fn load(&self, config: &DeviceConfig) -> Result<HalInst, HalError> {
+        let (vid, pid) = match &config.ftdi_dev {
+            Some(d) if !d.is_empty() => parse_vid_pid(d)?,
+            _ => (FTDI_VID, FTDI_PID),
+        };
+
+        Self::new(vid, pid, &config.spi, &config.pins)
+    }
+
+    fn load_pins(&self, bus: &HalInst, pins: &PinConfig) -> Result<HalPins, HalError> {
+        match &bus.base {
+            HalBase::Ftdi(ft) => Self::connect_pins(ft, pins),
+            _ => Err(HalError::InvalidConfig(
+                "Shared ftdi bus requires an ftdi instance".into(),
+            )),
+        }
+    }
+}
+
+impl FtdiDriver {
+    /// Load an FTDI MPSSE device using the provided configuration
+    pub fn new(
+        vid: u16,
+        pid: u16,
+        spi_config: &SpiConfig,
+        pins: &PinConfig,
+    ) -> Result<HalInst, HalError> {
+        debug!(
+            "Connecting to ftdi device {:04x}:{:04x} at {} baud with mode: {}",
+            vid, pid, spi_config.baud, spi_config.mode
+        );
+
+        // MPSSE supports sampling on the leading edge only
+        let polarity = match spi_config.mode {
+            0 => Polarity::IdleLow,
+            2 => Polarity::IdleHigh,
+            _ => return Err(HalError::InvalidSpiMode),
+        };
+
+        // Open device and initialise MPSSE
+        let device = ftdi::find_by_vid_pid(vid, pid)
+            .interface(ftdi::Interface::A)
+            .open()?;
+
+        let ft = ftdi_hal::FtHal::init_freq(device, spi_config.baud)?;
+
+        // Connect SPI (AD0: SCK, AD1: MOSI, AD2: MISO)
+        let mut spi = ft.spi()?;
+        spi.set_clock_polarity(polarity);
+
+        // Connect pins
+        let pins = Self::connect_pins(&ft, pins)?;
+
+        Ok(HalInst {
+            base: HalBase::Ftdi(ft),
+            spi: HalSpi::Ftdi(FtdiSpi(spi)),
+            pins,
+        })
+    }
+
+    /// Connect pins on an FTDI instance using the provided configuration
+    fn connect_pins(ft: &ftdi_hal::FtHal<Device>, pins: &PinConfig) -> Result<HalPins, HalError> {
+        let chip_select = output_pin(ft, &pins.chip_select, Some(PinState::High))?;
+
+        let reset = output_pin(ft, &pins.reset, None)?;
+
+        let busy = match &pins.busy {
+            Some(p) => input_pin(ft, p)?,
+            None => HalInputPin::None,
+        };
+
+        let ready = match &pins.ready {
+            Some(p) => input_pin(ft, p)?,
+            None => HalInputPin::None,
+        };
+
+        let led0 = match &pins.led0 {
+            Some(p) => output_pin(ft, p, None)?,
+            None => HalOutputPin::None,
+        };
+
+        let led1 = match &pins.led1 {
+            Some(p) => output_pin(ft, p, None)?,
+            None => HalOutputPin::None,
+        };
+
+        Ok(HalPins {
+            cs: chip_select,
+            reset,
+            busy,
+            ready,
+            led0,
+            led1,
+        })
+    }
+}
+
+/// Parse a `VID:PID` hex device identifier
+fn parse_vid_pid(s: &str) -> Result<(u16, u16), HalError> {
+    let ids = s
+        .split_once(':')
+        .map(|(v, p)| (u16::from_str_radix(v, 16), u16::from_str_radix(p, 16)));
+
+    match ids {
+        Some((Ok(vid), Ok(pid))) => Ok((vid, pid)),
+        _ => Err(HalError::InvalidConfig(format!(
+            "Invalid ftdi device '{}', expected VID:PID",
+            s
+        ))),
+    }
+}
+
+/// Load an output pin by ADBUS index (AD3..AD7, AD0..AD2 are used for SPI),
+/// with active-low inversion and initial levels applied in software
+fn output_pin(
+    ft: &ftdi_hal::FtHal<Device>,
+    pin: &PinSpec,
+    default: Option<PinState>,
+) -> Result<HalOutputPin, HalError> {
+    pin.check_support("ftdi", false, false)?;
+
+    debug!("Connecting to ftdi output pin: AD{}", pin);
+
+    let p = match pin.index {
+        3 => ft.ad3()?,
+        4 => ft.ad4()?,
+        5 => ft.ad5()?,
+        6 => ft.ad6()?,
+        7 => ft.ad7()?,
+        _ => {
+            return Err(HalError::InvalidConfig(format!(
+                "Invalid ftdi pin AD{}, only AD3..AD7 are available",
+                pin
+            )));
+        }
+    };
+
+    HalOutputPin::Ftdi(p).configure(pin, default)
+}
+
+/// Load an input pin by ADBUS index (AD3..AD7, AD0..AD2 are used for SPI)
+fn input_pin(ft: &ftdi_hal::FtHal<Device>, pin: &PinSpec) -> Result<HalInputPin, HalError> {
+    pin.check_support("ftdi", false, false)?;
+
+    debug!("Connecting to ftdi input pin: AD{}", pin);
+
+    let p = match pin.index {
+        3 => ft.adi3()?,
+        4 => ft.adi4()?,
+        5 => ft.adi5()?,
+        6 => ft.adi6()?,
+        7 => ft.adi7()?,
+        _ => {
+            return Err(HalError::InvalidConfig(format!(
+                "Invalid ftdi pin AD{}, only AD3..AD7 are available",
+                pin
+            )));
+        }
+    };
+
+    Ok(HalInputPin::Ftdi(p).with_active_low(pin.active_low))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_vid_pid() {
+        assert_eq!(parse_vid_pid("0403:6014").unwrap(), (0x0403, 0x6014));
+        assert!(parse_vid_pid("0403").is_err());
+        assert!(parse_vid_pid("zzzz:6014").is_err());
+    }
+}
@@ -1,14 +1,81 @@
+//! Linux spidev and GPIO character device backend
+//!
+//! Pins are line offsets on the GPIO chip set by the `gpio_chip` backend option
+//! (defaulting to `/dev/gpiochip0`), with active-low, bias and drive settings
+//! applied by the kernel when lines are requested.
+
 extern crate linux_embedded_hal;
-pub use linux_embedded_hal::sysfs_gpio::{Direction, Error as PinError};
-pub use linux_embedded_hal::{spidev, spidev::SpiModeFlags, Delay, Spidev, SysfsPin as Pindev};
+pub use linux_embedded_hal::{spidev, spidev::SpiModeFlags, Delay, Spidev};
+
+use std::convert::TryFrom;
+use std::os::unix::io::AsRawFd;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use gpio_cdev::{
+    Chip, EventRequestFlags, EventType, LineEventHandle, LineHandle, LineRequestFlags,
+};
 
 use super::*;
 
+/// Default GPIO chip for pins where the `gpio_chip` option is not set
+pub const DEFAULT_GPIO_CHIP: &str = "/dev/gpiochip0";
+
+/// Kernel line request bias flags (`GPIOHANDLE_REQUEST_BIAS_PULL_*`, linux 5.5+)
+/// which are not exposed by gpio-cdev
+const REQUEST_BIAS_PULL_UP: u32 = 1 << 5;
+const REQUEST_BIAS_PULL_DOWN: u32 = 1 << 6;
+
+/// Consumer label for requested lines without a pin label
+const CONSUMER: &str = "driver-pal";
+
 pub struct LinuxDriver;
 
+/// Linux spidev / GPIO character device backend, selected by `backend = "linux"` or by `spi_dev`
+impl HalBackend for LinuxDriver {
+    fn name(&self) -> &'static str {
+        "linux"
+    }
+
+    fn detect(&self, config: &DeviceConfig) -> bool {
+        config.spi_dev.is_some()
+    }
+
+    fn load(&self, config: &DeviceConfig) -> Result<HalInst, HalError> {
+        let path = match &config.spi_dev {
+            Some(p) => p,
+            None => {
+                return Err(HalError::InvalidConfig(
+                    "Linux backend requires an spi_dev".into(),
+                ));
+            }
+        };
+
+        let chip = config
+            .option::<String>("gpio_chip")?
+            .unwrap_or_else(|| DEFAULT_GPIO_CHIP.to_string());
+
+        Self::new(path, &chip, &config.spi, &config.pins)
+    }
+
+    fn load_pins(&self, bus: &HalInst, pins: &PinConfig) -> Result<HalPins, HalError> {
+        match &bus.base {
+            HalBase::Linux(chip) => Self::connect_pins(chip, pins),
+            _ => Err(HalError::InvalidConfig(
+                "Shared linux bus requires a linux instance".into(),
+            )),
+        }
+    }
+}
+
 impl LinuxDriver {
-    /// Load an SPI device using the provided configuration
-    pub fn new(path: &str, spi: &SpiConfig, pins: &PinConfig) -> Result<HalInst, HalError> {
+    /// Load an SPI device and pins on the provided GPIO chip using the provided configuration
+    pub fn new(
+        path: &str,
+        gpio_chip: &str,
+        spi: &SpiConfig,
+        pins: &PinConfig,
+    ) -> Result<HalInst, HalError> {
         let mut flags = match spi.mode {
             0 => SpiModeFlags::SPI_MODE_0,
             1 => SpiModeFlags::SPI_MODE_1,
@@ -26,38 +93,44 @@ impl LinuxDriver {
 
         let spi = load_spi(path, spi.baud, flags)?;
 
-        let pins = Self::load_pins(pins)?;
+        let chip = Chip::new(gpio_chip).context("opening gpio chip", gpio_chip)?;
+        let chip = Arc::new(Mutex::new(chip));
+
+        let pins = Self::connect_pins(&chip, pins)?;
 
         Ok(HalInst {
-            base: HalBase::None,
+            base: HalBase::Linux(chip),
             spi: HalSpi::Linux(spi),
             pins,
         })
     }
 
     /// Load pins using the provided config
-    fn load_pins(pins: &PinConfig) -> Result<HalPins, HalError> {
-        let chip_select = load_pin(pins.chip_select, Direction::Out)?;
+    fn connect_pins(chip: &Arc<Mutex<Chip>>, pins: &PinConfig) -> Result<HalPins, HalError> {
+        let mut chip = chip.lock().unwrap();
 
-        let reset = load_pin(pins.reset, Direction::Out)?;
+        // CS and reset idle in their inactive (high) state unless otherwise specified
+        let chip_select = load_output(&mut chip, &pins.chip_select, PinState::High)?;
 
-        let busy = match pins.busy {
-            Some(p) => HalInputPin::Linux(load_pin(p, Direction::In)?),
+        let reset = load_output(&mut chip, &pins.reset, PinState::High)?;
+
+        let busy = match &pins.busy {
+            Some(p) => HalInputPin::Linux(load_input(&mut chip, p)?),
             None => HalInputPin::None,
         };
 
-        let ready = match pins.ready {
-            Some(p) => HalInputPin::Linux(load_pin(p, Direction::In)?),
+        let ready = match &pins.ready {
+            Some(p) => HalInputPin::Linux(load_input(&mut chip, p)?),
             None => HalInputPin::None,
         };
 
-        let led0 = match pins.led0 {
-            Some(p) => HalOutputPin::Linux(load_pin(p, Direction::Out)?),
+        let led0 = match &pins.led0 {
+            Some(p) => HalOutputPin::Linux(load_output(&mut chip, p, PinState::Low)?),
             None => HalOutputPin::None,
         };
 
-        let led1 = match pins.led1 {
-            Some(p) => HalOutputPin::Linux(load_pin(p, Direction::Out)?),
+        let led1 = match &pins.led1 {
+            Some(p) => HalOutputPin::Linux(load_output(&mut chip, p, PinState::Low)?),
             None => HalOutputPin::None,
         };
 
@@ -81,26 +154,180 @@ fn load_spi(path: &str, baud: u32, mode: spidev::SpiModeFlags) -> Result<Spidev,
         path, baud, mode
     );
 
-    let mut spi = Spidev::open(path)?;
+    let mut spi = Spidev::open(path).context("opening spi device", path)?;
 
     let mut config = spidev::SpidevOptions::new();
     config.mode(SpiModeFlags::SPI_MODE_0 | SpiModeFlags::SPI_NO_CS);
     config.max_speed_hz(baud);
-    spi.configure(&config)?;
+    spi.configure(&config)
+        .context("configuring spi device", path)?;
 
     Ok(spi)
 }
 
-/// Load a Pin using the provided configuration
-fn load_pin(index: u64, direction: Direction) -> Result<Pindev, HalError> {
+/// Build line request flags for a pin, active-low inversion, bias and drive
+/// are applied by the kernel so levels and edges are logical
+fn request_flags(pin: &PinSpec, output: bool) -> LineRequestFlags {
+    let mut flags = match output {
+        true => LineRequestFlags::OUTPUT,
+        false => LineRequestFlags::INPUT,
+    };
+
+    if pin.active_low {
+        flags |= LineRequestFlags::ACTIVE_LOW;
+    }
+
+    if let Some(PinDrive::OpenDrain) = pin.drive {
+        flags |= LineRequestFlags::OPEN_DRAIN;
+    }
+
+    let bias = match pin.bias {
+        Some(PinBias::PullUp) => REQUEST_BIAS_PULL_UP,
+        Some(PinBias::PullDown) => REQUEST_BIAS_PULL_DOWN,
+        None => 0,
+    };
+
+    // Safety: bias bits are defined by the kernel ABI and passed through unchanged
+    flags | unsafe { LineRequestFlags::from_bits_unchecked(bias) }
+}
+
+/// Fetch the GPIO line offset for a pin
+fn line_offset(pin: &PinSpec) -> Result<u32, HalError> {
+    u32::try_from(pin.index)
+        .map_err(|_| HalError::InvalidConfig(format!("Invalid GPIO line offset {}", pin)))
+}
+
+/// Load an output pin, driven to the initial (or provided default) logical level
+fn load_output(
+    chip: &mut Chip,
+    pin: &PinSpec,
+    default: PinState,
+) -> Result<LinuxOutputPin, HalError> {
+    let flags = request_flags(pin, true);
+    let level = match pin.initial.clone().unwrap_or(default) {
+        PinState::High => 1,
+        PinState::Low => 0,
+    };
+
     debug!(
-        "Connecting to pin: {} with direction: {:?}",
-        index, direction
+        "Connecting to output pin: {} with flags: {:?} level: {}",
+        pin, flags, level
     );
 
-    let p = Pindev::new(index);
-    p.export()?;
-    p.set_direction(direction)?;
+    let line = chip
+        .get_line(line_offset(pin)?)
+        .context("fetching pin", pin)?;
+    let handle = line
+        .request(flags, level, pin.label.as_deref().unwrap_or(CONSUMER))
+        .context("requesting pin", pin)?;
+
+    Ok(LinuxOutputPin(handle))
+}
 
-    Ok(p)
+/// Load an input pin, requested for edge events to support `WaitForEdge`
+fn load_input(chip: &mut Chip, pin: &PinSpec) -> Result<LinuxInputPin, HalError> {
+    pin.check_support("linux", true, false)?;
+
+    let flags = request_flags(pin, false);
+
+    debug!("Connecting to input pin: {} with flags: {:?}", pin, flags);
+
+    let line = chip
+        .get_line(line_offset(pin)?)
+        .context("fetching pin", pin)?;
+    let events = line
+        .events(
+            flags,
+            EventRequestFlags::BOTH_EDGES,
+            pin.label.as_deref().unwrap_or(CONSUMER),
+        )
+        .context("requesting pin", pin)?;
+
+    Ok(LinuxInputPin(events))
+}
+
+/// Linux GPIO character device output pin
+pub struct LinuxOutputPin(LineHandle);
+
+impl embedded_hal::digital::ErrorType for LinuxOutputPin {
+    type Error = HalError;
+}
+
+impl embedded_hal::digital::OutputPin for LinuxOutputPin {
+    fn set_high(&mut self) -> Result<(), HalError> {
+        Ok(self.0.set_value(1)?)
+    }
+
+    fn set_low(&mut self) -> Result<(), HalError> {
+        Ok(self.0.set_value(0)?)
+    }
+}
+
+/// Linux GPIO character device input pin, requested for line events so
+/// edge waits block on the kernel rather than polling
+pub struct LinuxInputPin(LineEventHandle);
+
+impl embedded_hal::digital::ErrorType for LinuxInputPin {
+    type Error = HalError;
+}
+
+impl embedded_hal::digital::InputPin for LinuxInputPin {
+    fn is_high(&self) -> Result<bool, HalError> {
+        Ok(self.0.get_value()? != 0)
+    }
+
+    fn is_low(&self) -> Result<bool, HalError> {
+        Ok(self.0.get_value()? == 0)
+    }
+}
+
+impl LinuxInputPin {
+    /// Wait for an edge using line events and poll(2), edges are reported
+    /// by the kernel in logical (active-low adjusted) terms
+    ///
+    /// Edges queued since the last wait are consumed first, so an edge occurring
+    /// between reading the pin and starting the wait returns immediately
+    pub(crate) fn wait_for_edge(
+        &mut self,
+        edge: Edge,
+        timeout: Duration,
+    ) -> Result<bool, HalError> {
+        let start = Instant::now();
+
+        loop {
+            // Queued events are still read once the timeout has elapsed
+            let remaining = timeout.saturating_sub(start.elapsed());
+
+            if !poll_readable(&self.0, remaining)? {
+                return Ok(false);
+            }
+
+            let e = self.0.get_event()?;
+
+            match (edge, e.event_type()) {
+                (Edge::Both, _) => return Ok(true),
+                (Edge::Rising, EventType::RisingEdge) => return Ok(true),
+                (Edge::Falling, EventType::FallingEdge) => return Ok(true),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Wait for a file descriptor to become readable, returning `false` on timeout
+fn poll_readable<F: AsRawFd>(f: &F, timeout: Duration) -> Result<bool, HalError> {
+    let mut fds = libc::pollfd {
+        fd: f.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    // Round up so sub-millisecond timeouts still wait
+    let timeout_ms = ((timeout.as_nanos() + 999_999) / 1_000_000).min(i32::MAX as u128) as i32;
+
+    match unsafe { libc::poll(&mut fds, 1, timeout_ms) } {
+        -1 => Err(std::io::Error::last_os_error().into()),
+        0 => Ok(false),
+        _ => Ok(true),
+    }
 }
@@ -0,0 +1,674 @@
+//! MCP2210 USB-SPI bridge backend
+//!
+//! This implements the MCP2210 HID command protocol over a generic `Transport`,
+//! with a linux `hidraw` transport provided for hardware use.
+
+use std::boxed::Box;
+use std::convert::TryFrom;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+use embedded_hal::delay::DelayUs;
+use embedded_hal::spi::Operation;
+
+use super::{
+    DeviceConfig, HalBackend, HalBase, HalDelay, HalError, HalInputPin, HalInst, HalOutputPin,
+    HalPins, HalSpi, PinConfig, PinSpec, ResultExt, SpiConfig,
+};
+use crate::PinState;
+
+/// MCP2210 HID report length
+pub const REPORT_LEN: usize = 64;
+
+/// Maximum SPI payload per transfer command
+const CHUNK_LEN: usize = 60;
+
+/// Number of general purpose pins
+pub const NUM_PINS: u64 = 9;
+
+const CMD_SET_CHIP_SETTINGS: u8 = 0x21;
+const CMD_SET_GPIO_VALUE: u8 = 0x30;
+const CMD_GET_GPIO_VALUE: u8 = 0x31;
+const CMD_SET_GPIO_DIRECTION: u8 = 0x32;
+const CMD_SET_SPI_SETTINGS: u8 = 0x40;
+const CMD_SPI_TRANSFER: u8 = 0x42;
+
+const STATUS_OK: u8 = 0x00;
+/// SPI bus is in use by an external master
+pub(crate) const STATUS_BUS_UNAVAILABLE: u8 = 0xF7;
+const STATUS_IN_PROGRESS: u8 = 0xF8;
+
+const ENGINE_FINISHED: u8 = 0x10;
+
+/// Maximum consecutive transfer commands without progress before timing out
+const MAX_TRANSFER_RETRIES: usize = 1_000;
+
+/// Minimum supported SPI bit rate
+const MIN_BAUD: u32 = 1_464;
+/// Maximum supported SPI bit rate
+const MAX_BAUD: u32 = 12_000_000;
+
+/// Transport trait for exchanging MCP2210 HID reports
+pub trait Transport: Send {
+    /// Send a command report and return the response report
+    fn command(&mut self, req: &[u8; REPORT_LEN]) -> Result<[u8; REPORT_LEN], HalError>;
+}
+
+/// Linux hidraw transport
+pub struct Hidraw {
+    file: File,
+}
+
+impl Hidraw {
+    /// Open a hidraw device by path (eg. `/dev/hidraw0`)
+    pub fn open(path: &str) -> Result<Self, HalError> {
+        debug!("Connecting to mcp2210 hidraw device: {}", path);
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .context("opening hidraw device", path)?;
+
+        Ok(Self { file })
+    }
+}
+
+impl Transport for Hidraw {
+    fn command(&mut self, req: &[u8; REPORT_LEN]) -> Result<[u8; REPORT_LEN], HalError> {
+        // Unnumbered reports are prefixed with a zero report ID
+        let mut out = [0u8; REPORT_LEN + 1];
+        out[1..].copy_from_slice(req);
+        self.file.write_all(&out)?;
+
+        let mut resp = [0u8; REPORT_LEN];
+        self.file.read_exact(&mut resp)?;
+
+        Ok(resp)
+    }
+}
+
+/// MCP2210 device, shared between the SPI and pin objects
+pub struct Mcp2210 {
+    transport: Box<dyn Transport>,
+    baud: u32,
+    mode: u8,
+    transfer_len: Option<u16>,
+    gpio_value: u16,
+    gpio_direction: u16,
+}
+
+impl Mcp2210 {
+    /// Create an MCP2210 instance using the provided transport,
+    /// configuring all pins as GPIO inputs
+    pub fn new(transport: Box<dyn Transport>, baud: u32, mode: u8) -> Result<Self, HalError> {
+        if baud < MIN_BAUD || baud > MAX_BAUD {
+            return Err(HalError::InvalidConfig(format!(
+                "Unsupported mcp2210 baud rate: {} (range {}..{})",
+                baud, MIN_BAUD, MAX_BAUD
+            )));
+        }
+
+        if mode > 3 {
+            return Err(HalError::InvalidSpiMode);
+        }
+
+        let mut d = Self {
+            transport,
+            baud,
+            mode,
+            transfer_len: None,
+            gpio_value: 0,
+            gpio_direction: 0x01FF,
+        };
+
+        // Designate all pins as GPIO, CS is managed via the HAL
+        let mut req = [0u8; REPORT_LEN];
+        req[0] = CMD_SET_CHIP_SETTINGS;
+        req[13..15].copy_from_slice(&d.gpio_value.to_le_bytes());
+        req[15..17].copy_from_slice(&d.gpio_direction.to_le_bytes());
+        d.command(&req)?;
+
+        Ok(d)
+    }
+
+    /// Execute a command, checking the response status
+    fn command(&mut self, req: &[u8; REPORT_LEN]) -> Result<[u8; REPORT_LEN], HalError> {
+        let resp = self.transport.command(req)?;
+
+        if resp[0] != req[0] {
+            error!(
+                "Unexpected mcp2210 response 0x{:02x} to command 0x{:02x}",
+                resp[0], req[0]
+            );
+            return Err(HalError::Mcp2210(resp[1]));
+        }
+
+        match resp[1] {
+            STATUS_OK | STATUS_IN_PROGRESS => Ok(resp),
+            s => Err(HalError::Mcp2210(s)),
+        }
+    }
+
+    /// Update SPI transfer settings for a transfer of the provided length
+    fn set_transfer_len(&mut self, len: u16) -> Result<(), HalError> {
+        if self.transfer_len == Some(len) {
+            return Ok(());
+        }
+
+        let mut req = [0u8; REPORT_LEN];
+        req[0] = CMD_SET_SPI_SETTINGS;
+        req[4..8].copy_from_slice(&self.baud.to_le_bytes());
+        // Idle and active CS values (unused, all pins are GPIO)
+        req[8..10].copy_from_slice(&0x01FFu16.to_le_bytes());
+        req[10..12].copy_from_slice(&0x01FFu16.to_le_bytes());
+        // CS to data, data to CS and inter-byte delays left at zero
+        req[18..20].copy_from_slice(&len.to_le_bytes());
+        req[20] = self.mode;
+
+        self.command(&req)?;
+        self.transfer_len = Some(len);
+
+        Ok(())
+    }
+
+    /// Execute a full-duplex SPI transfer in place
+    pub fn transfer(&mut self, data: &mut [u8]) -> Result<(), HalError> {
+        for c in data.chunks_mut(u16::MAX as usize) {
+            self.transfer_chunk(c)?;
+        }
+        Ok(())
+    }
+
+    fn transfer_chunk(&mut self, data: &mut [u8]) -> Result<(), HalError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.set_transfer_len(data.len() as u16)?;
+
+        let mut tx = 0;
+        let mut rx = Vec::with_capacity(data.len());
+        let mut retries = 0;
+
+        loop {
+            let n = usize::min(CHUNK_LEN, data.len() - tx);
+
+            let mut req = [0u8; REPORT_LEN];
+            req[0] = CMD_SPI_TRANSFER;
+            req[1] = n as u8;
+            req[4..4 + n].copy_from_slice(&data[tx..tx + n]);
+
+            let resp = self.command(&req)?;
+
+            // Chunk not accepted while the engine is busy, retry
+            if resp[1] == STATUS_IN_PROGRESS {
+                retries += 1;
+            } else {
+                tx += n;
+
+                let rx_len = usize::min(resp[2] as usize, CHUNK_LEN);
+                rx.extend_from_slice(&resp[4..4 + rx_len]);
+
+                if resp[3] == ENGINE_FINISHED {
+                    break;
+                }
+
+                // Polling for pending data counts as a retry until data arrives
+                match n + rx_len {
+                    0 => retries += 1,
+                    _ => retries = 0,
+                }
+            }
+
+            if retries > MAX_TRANSFER_RETRIES {
+                error!(
+                    "mcp2210 transfer timeout (sent: {} received: {} of {})",
+                    tx,
+                    rx.len(),
+                    data.len()
+                );
+                return Err(HalError::Timeout);
+            }
+        }
+
+        if rx.len() != data.len() {
+            error!(
+                "mcp2210 transfer length mismatch (sent: {} received: {})",
+                data.len(),
+                rx.len()
+            );
+            return Err(HalError::Mcp2210(STATUS_BUS_UNAVAILABLE));
+        }
+
+        data.copy_from_slice(&rx);
+
+        Ok(())
+    }
+
+    /// Set a GPIO pin direction
+    fn set_gpio_direction(&mut self, index: u64, input: bool) -> Result<(), HalError> {
+        let mask = 1 << index;
+        match input {
+            true => self.gpio_direction |= mask,
+            false => self.gpio_direction &= !mask,
+        }
+
+        let mut req = [0u8; REPORT_LEN];
+        req[0] = CMD_SET_GPIO_DIRECTION;
+        req[4..6].copy_from_slice(&self.gpio_direction.to_le_bytes());
+        self.command(&req)?;
+
+        Ok(())
+    }
+
+    /// Set a GPIO output level
+    fn set_gpio(&mut self, index: u64, high: bool) -> Result<(), HalError> {
+        let mask = 1 << index;
+        match high {
+            true => self.gpio_value |= mask,
+            false => self.gpio_value &= !mask,
+        }
+
+        let mut req = [0u8; REPORT_LEN];
+        req[0] = CMD_SET_GPIO_VALUE;
+        req[4..6].copy_from_slice(&self.gpio_value.to_le_bytes());
+        self.command(&req)?;
+
+        Ok(())
+    }
+
+    /// Fetch a GPIO input level
+    fn get_gpio(&mut self, index: u64) -> Result<bool, HalError> {
+        let mut req = [0u8; REPORT_LEN];
+        req[0] = CMD_GET_GPIO_VALUE;
+        let resp = self.command(&req)?;
+
+        let v = u16::from_le_bytes([resp[4], resp[5]]);
+
+        Ok(v & (1 << index) != 0)
+    }
+}
+
+/// MCP2210 SPI device
+pub struct Mcp2210Spi(Arc<Mutex<Mcp2210>>);
+
+impl embedded_hal::spi::SpiDevice<u8> for Mcp2210Spi {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        let mut d = self.0.lock().unwrap();
+
+        for o in operations {
+            match o {
+                Operation::Read(r) => {
+                    r.iter_mut().for_each(|b| *b = 0);
+                    d.transfer(r)?;
+                }
+                Operation::Write(w) => {
+                    let mut b = w.to_vec();
+                    d.transfer(&mut b)?;
+                }
+                Operation::Transfer(r, w) => {
+                    let mut b = w.to_vec();
+                    b.resize(usize::max(r.len(), w.len()), 0);
+                    d.transfer(&mut b)?;
+                    let n = r.len();
+                    r.copy_from_slice(&b[..n]);
+                }
+                Operation::TransferInPlace(b) => d.transfer(b)?,
+                Operation::DelayUs(us) => HalDelay::new().delay_us(*us),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl embedded_hal::spi::ErrorType for Mcp2210Spi {
+    type Error = HalError;
+}
+
+/// MCP2210 GP pin
+pub struct Mcp2210Pin {
+    dev: Arc<Mutex<Mcp2210>>,
+    index: u64,
+}
+
+impl embedded_hal::digital::InputPin for Mcp2210Pin {
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.dev.lock().unwrap().get_gpio(self.index)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.is_high()?)
+    }
+}
+
+impl embedded_hal::digital::OutputPin for Mcp2210Pin {
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.dev.lock().unwrap().set_gpio(self.index, true)
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.dev.lock().unwrap().set_gpio(self.index, false)
+    }
+}
+
+impl embedded_hal::digital::ErrorType for Mcp2210Pin {
+    type Error = HalError;
+}
+
+/// MCP2210 `Hal` implementation
+pub struct Mcp2210Driver;
+
+/// MCP2210 backend, selected by `backend = "mcp2210"` or by `mcp2210_dev`
+impl HalBackend for Mcp2210Driver {
+    fn name(&self) -> &'static str {
+        "mcp2210"
+    }
+
+    fn detect(&self, config: &DeviceConfig) -> bool {
+        config.mcp2210_dev.is_some()
+    }
+
+    fn load(&self, config: &DeviceConfig) -> Result<HalInst, HalError> {
+        let path = match &config.mcp2210_dev {
+            Some(p) => p,
+            None => {
+                return Err(HalError::InvalidConfig(
+                    "MCP2210 backend requires an mcp2210_dev".into(),
+                ));
+            }
+        };
+
+        let transport = Hidraw::open(path)?;
+
+        Self::new(Box::new(transport), &config.spi, &config.pins)
+    }
+
+    fn load_pins(&self, bus: &HalInst, pins: &PinConfig) -> Result<HalPins, HalError> {
+        match &bus.base {
+            HalBase::Mcp2210(dev) => Self::connect_pins(dev, pins),
+            _ => Err(HalError::InvalidConfig(
+                "Shared mcp2210 bus requires an mcp2210 instance".into(),
+            )),
+        }
+    }
+}
+
+impl Mcp2210Driver {
+    /// Load an MCP2210 device using the provided transport and configuration
+    pub fn new(
+        transport: Box<dyn Transport>,
+        spi_config: &SpiConfig,
+        pins: &PinConfig,
+    ) -> Result<HalInst, HalError> {
+        debug!(
+            "Connecting to mcp2210 at {} baud with mode: {}",
+            spi_config.baud, spi_config.mode
+        );
+
+        let mode = u8::try_from(spi_config.mode).map_err(|_| HalError::InvalidSpiMode)?;
+        let dev = Mcp2210::new(transport, spi_config.baud, mode)?;
+        let dev = Arc::new(Mutex::new(dev));
+
+        let pins = Self::connect_pins(&dev, pins)?;
+
+        Ok(HalInst {
+            base: HalBase::Mcp2210(dev.clone()),
+            spi: HalSpi::Mcp2210(Mcp2210Spi(dev)),
+            pins,
+        })
+    }
+
+    /// Connect pins on an MCP2210 instance using the provided configuration,
+    /// with active-low inversion and initial levels applied in software
+    fn connect_pins(dev: &Arc<Mutex<Mcp2210>>, pins: &PinConfig) -> Result<HalPins, HalError> {
+        let chip_select = output_pin(dev, &pins.chip_select, Some(PinState::High))?;
+
+        let reset = output_pin(dev, &pins.reset, None)?;
+
+        let busy = match &pins.busy {
+            Some(p) => input_pin(dev, p)?,
+            None => HalInputPin::None,
+        };
+
+        let ready = match &pins.ready {
+            Some(p) => input_pin(dev, p)?,
+            None => HalInputPin::None,
+        };
+
+        let led0 = match &pins.led0 {
+            Some(p) => output_pin(dev, p, None)?,
+            None => HalOutputPin::None,
+        };
+
+        let led1 = match &pins.led1 {
+            Some(p) => output_pin(dev, p, None)?,
+            None => HalOutputPin::None,
+        };
+
+        Ok(HalPins {
+            cs: chip_select,
+            reset,
+            busy,
+            ready,
+            led0,
+            led1,
+        })
+    }
+}
+
+/// Load a GP output pin with the provided default (logical) level
+fn output_pin(
+    dev: &Arc<Mutex<Mcp2210>>,
+    pin: &PinSpec,
+    default: Option<PinState>,
+) -> Result<HalOutputPin, HalError> {
+    let p = load_pin(dev, pin, false)?;
+
+    HalOutputPin::Mcp2210(p).configure(pin, default)
+}
+
+/// Load a GP input pin
+fn input_pin(dev: &Arc<Mutex<Mcp2210>>, pin: &PinSpec) -> Result<HalInputPin, HalError> {
+    let p = load_pin(dev, pin, true)?;
+
+    Ok(HalInputPin::Mcp2210(p).with_active_low(pin.active_low))
+}
+
+/// Load a GP pin with the provided direction
+fn load_pin(dev: &Arc<Mutex<Mcp2210>>, pin: &PinSpec, input: bool) -> Result<Mcp2210Pin, HalError> {
+    debug!("Connecting to mcp2210 pin: GP{} (input: {})", pin, input);
+
+    pin.check_support("mcp2210", false, false)?;
+
+    if pin.index >= NUM_PINS {
+        return Err(HalError::InvalidConfig(format!(
+            "Invalid mcp2210 pin GP{}, only GP0..GP8 are available",
+            pin
+        )));
+    }
+
+    dev.lock().unwrap().set_gpio_direction(pin.index, input)?;
+
+    Ok(Mcp2210Pin {
+        dev: dev.clone(),
+        index: pin.index,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal::digital::{InputPin, OutputPin};
+    use embedded_hal::spi::SpiDevice;
+
+    /// Loopback stand-in for an MCP2210 with MOSI tied to MISO
+    #[derive(Default)]
+    struct Loopback {
+        transfer_len: usize,
+        sent: usize,
+        pending: Vec<u8>,
+        gpio_value: u16,
+        gpio_direction: u16,
+        stalled: bool,
+    }
+
+    impl Transport for Arc<Mutex<Loopback>> {
+        fn command(&mut self, req: &[u8; REPORT_LEN]) -> Result<[u8; REPORT_LEN], HalError> {
+            let mut s = self.lock().unwrap();
+            let mut resp = [0u8; REPORT_LEN];
+            resp[0] = req[0];
+
+            match req[0] {
+                CMD_SET_SPI_SETTINGS => {
+                    s.transfer_len = u16::from_le_bytes([req[18], req[19]]) as usize;
+                }
+                CMD_SPI_TRANSFER if s.stalled => resp[1] = STATUS_IN_PROGRESS,
+                CMD_SPI_TRANSFER => {
+                    let n = req[1] as usize;
+                    s.pending.extend_from_slice(&req[4..4 + n]);
+                    s.sent += n;
+
+                    // Return at most one chunk of received data per command
+                    let rx = usize::min(s.pending.len(), CHUNK_LEN);
+                    let d: Vec<_> = s.pending.drain(..rx).collect();
+                    resp[2] = rx as u8;
+                    resp[4..4 + rx].copy_from_slice(&d);
+
+                    if s.sent == s.transfer_len && s.pending.is_empty() {
+                        resp[3] = ENGINE_FINISHED;
+                        s.sent = 0;
+                    }
+                }
+                CMD_SET_GPIO_VALUE => s.gpio_value = u16::from_le_bytes([req[4], req[5]]),
+                CMD_GET_GPIO_VALUE => resp[4..6].copy_from_slice(&s.gpio_value.to_le_bytes()),
+                CMD_SET_GPIO_DIRECTION => s.gpio_direction = u16::from_le_bytes([req[4], req[5]]),
+                _ => (),
+            }
+
+            Ok(resp)
+        }
+    }
+
+    fn pins() -> PinConfig {
+        PinConfig {
+            chip_select: PinSpec::new(0),
+            reset: PinSpec::new(1),
+            busy: Some(PinSpec::new(2)),
+            ready: None,
+            led0: None,
+            led1: None,
+        }
+    }
+
+    #[test]
+    fn test_loopback_transfer() {
+        let l = Arc::new(Mutex::new(Loopback::default()));
+        let spi = SpiConfig {
+            baud: 1_000_000,
+            mode: 0,
+        };
+
+        let mut h = Mcp2210Driver::new(Box::new(l.clone()), &spi, &pins()).unwrap();
+
+        let outgoing: Vec<u8> = (0..150).map(|v| v as u8).collect();
+        let mut d = outgoing.clone();
+        h.spi.transfer_in_place(&mut d).unwrap();
+
+        assert_eq!(outgoing, d);
+    }
+
+    #[test]
+    fn test_transfer_timeout() {
+        let l = Arc::new(Mutex::new(Loopback::default()));
+        let spi = SpiConfig {
+            baud: 1_000_000,
+            mode: 0,
+        };
+
+        let mut h = Mcp2210Driver::new(Box::new(l.clone()), &spi, &pins()).unwrap();
+        l.lock().unwrap().stalled = true;
+
+        let mut d = [0x00; 4];
+        let r = h.spi.transfer_in_place(&mut d);
+        assert!(matches!(r, Err(HalError::Timeout)));
+    }
+
+    #[test]
+    fn test_error_kind() {
+        use embedded_hal::spi::{Error, ErrorKind};
+
+        assert_eq!(
+            HalError::Mcp2210(STATUS_BUS_UNAVAILABLE).kind(),
+            ErrorKind::ModeFault
+        );
+        assert_eq!(HalError::Mcp2210(0xFD).kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_gpio() {
+        let l = Arc::new(Mutex::new(Loopback::default()));
+        let spi = SpiConfig {
+            baud: 1_000_000,
+            mode: 0,
+        };
+
+        let mut h = Mcp2210Driver::new(Box::new(l.clone()), &spi, &pins()).unwrap();
+
+        // CS idles high, busy configured as input
+        assert_eq!(l.lock().unwrap().gpio_value, 0b001);
+        assert_eq!(l.lock().unwrap().gpio_direction, 0x01FF & !0b011);
+
+        h.pins.reset.set_high().unwrap();
+        assert_eq!(l.lock().unwrap().gpio_value, 0b011);
+
+        assert_eq!(h.pins.busy.is_high().unwrap(), false);
+        l.lock().unwrap().gpio_value |= 0b100;
+        assert_eq!(h.pins.busy.is_high().unwrap(), true);
+    }
+
+    #[test]
+    fn test_active_low() {
+        let l = Arc::new(Mutex::new(Loopback::default()));
+        let spi = SpiConfig {
+            baud: 1_000_000,
+            mode: 0,
+        };
+        let mut pins = pins();
+        pins.reset = "1,active-low,initial=low".parse().unwrap();
+        pins.busy = Some("2,active-low".parse().unwrap());
+
+        let h = Mcp2210Driver::new(Box::new(l.clone()), &spi, &pins).unwrap();
+
+        // Logical low reset is electrically high, busy reports logical state
+        assert_eq!(l.lock().unwrap().gpio_value, 0b011);
+        assert!(h.pins.busy.is_high().unwrap());
+        l.lock().unwrap().gpio_value |= 0b100;
+        assert!(h.pins.busy.is_low().unwrap());
+
+        pins.busy = Some("2,pull-up".parse().unwrap());
+        assert!(Mcp2210Driver::new(Box::new(l), &spi, &pins).is_err());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let l = Arc::new(Mutex::new(Loopback::default()));
+        let spi = SpiConfig {
+            baud: 100_000_000,
+            mode: 0,
+        };
+
+        assert!(Mcp2210Driver::new(Box::new(l.clone()), &spi, &pins()).is_err());
+
+        // Modes are not truncated into range
+        let spi = SpiConfig {
+            baud: 1_000_000,
+            mode: 256,
+        };
+        assert!(Mcp2210Driver::new(Box::new(l), &spi, &pins()).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Mock backend for exercising utilities without hardware
+//!
+//! Selected with `--backend mock`, SPI writes are looped back to reads, output pins
+//! are logged and input pins read the electrical levels set with the `busy` / `ready`
+//! backend options (defaulting to not busy and ready).
+
+use std::boxed::Box;
+
+use embedded_hal::delay::DelayUs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
+
+use super::{
+    DeviceConfig, HalBackend, HalBase, HalDelay, HalError, HalInputPin, HalInst, HalOutputPin,
+    HalPins, HalSpi, PinConfig, PinSpec,
+};
+use crate::PinState;
+
+/// Mock HAL backend
+pub struct MockDriver;
+
+impl HalBackend for MockDriver {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn load(&self, config: &DeviceConfig) -> Result<HalInst, HalError> {
+        let busy = config.option::<bool>("busy")?.unwrap_or(false);
+        let ready = config.option::<bool>("ready")?.unwrap_or(true);
+
+        Ok(HalInst {
+            base: HalBase::None,
+            spi: HalSpi::Dyn(Box::new(Loopback)),
+            pins: Self::pins(&config.pins, busy, ready)?,
+        })
+    }
+
+    fn load_pins(&self, _bus: &HalInst, pins: &PinConfig) -> Result<HalPins, HalError> {
+        Self::pins(pins, false, true)
+    }
+}
+
+impl MockDriver {
+    /// Create mock pins for the provided configuration
+    fn pins(pins: &PinConfig, busy: bool, ready: bool) -> Result<HalPins, HalError> {
+        let input = |spec: &Option<PinSpec>, level| match spec {
+            Some(p) => HalInputPin::Dyn(Box::new(Level(level))).with_active_low(p.active_low),
+            None => HalInputPin::None,
+        };
+        let output = |spec: &PinSpec, name, default| {
+            HalOutputPin::Dyn(Box::new(Logger(name))).configure(spec, default)
+        };
+
+        Ok(HalPins {
+            cs: output(&pins.chip_select, "cs", Some(PinState::High))?,
+            reset: output(&pins.reset, "reset", Some(PinState::High))?,
+            busy: input(&pins.busy, busy),
+            ready: input(&pins.ready, ready),
+            led0: match &pins.led0 {
+                Some(p) => output(p, "led0", None)?,
+                None => HalOutputPin::None,
+            },
+            led1: match &pins.led1 {
+                Some(p) => output(p, "led1", None)?,
+                None => HalOutputPin::None,
+            },
+        })
+    }
+}
+
+/// SPI device looping written data back to reads
+struct Loopback;
+
+impl ErrorType for Loopback {
+    type Error = HalError;
+}
+
+impl SpiDevice<u8> for Loopback {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), HalError> {
+        for o in operations {
+            match o {
+                Operation::Write(d) => debug!("SPI write: {:02x?}", d),
+                Operation::Read(d) => d.iter_mut().for_each(|v| *v = 0),
+                Operation::Transfer(r, w) => {
+                    debug!("SPI transfer: {:02x?}", w);
+                    for (i, v) in r.iter_mut().enumerate() {
+                        *v = w.get(i).cloned().unwrap_or(0);
+                    }
+                }
+                Operation::TransferInPlace(d) => debug!("SPI transfer: {:02x?}", d),
+                Operation::DelayUs(us) => HalDelay::new().delay_us(*us),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Input pin with a fixed level
+struct Level(bool);
+
+impl embedded_hal::digital::ErrorType for Level {
+    type Error = HalError;
+}
+
+impl InputPin for Level {
+    fn is_high(&self) -> Result<bool, HalError> {
+        Ok(self.0)
+    }
+
+    fn is_low(&self) -> Result<bool, HalError> {
+        Ok(!self.0)
+    }
+}
+
+/// Output pin logging set levels
+struct Logger(&'static str);
+
+impl embedded_hal::digital::ErrorType for Logger {
+    type Error = HalError;
+}
+
+impl OutputPin for Logger {
+    fn set_high(&mut self) -> Result<(), HalError> {
+        debug!("Set {} pin high", self.0);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), HalError> {
+        debug!("Set {} pin low", self.0);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::config::{parse_config, ConfigFormat};
+    use super::*;
+    use crate::{Busy, PrefixRead, Ready};
+
+    #[test]
+    fn test_mock_backend() {
+        let c: DeviceConfig = parse_config(
+            "a.toml",
+            "backend = \"mock\"\noptions = [\"busy=true\"]\nbusy = 5\nready = \"6,active-low\"",
+            ConfigFormat::Toml,
+        )
+        .unwrap();
+        let mut h = HalInst::load(&c).unwrap();
+
+        let mut d = [0x00; 2];
+        h.transfer(&mut d, &[0x01, 0x02]).unwrap();
+        assert_eq!(d, [0x01, 0x02]);
+
+        let mut d = [0xAA; 2];
+        h.prefix_read(&[0x10], &mut d).unwrap();
+        assert_eq!(d, [0xAA; 2]);
+
+        assert_eq!(h.get_busy().unwrap(), PinState::High);
+        assert_eq!(h.get_ready().unwrap(), PinState::Low);
+    }
+}
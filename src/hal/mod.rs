@@ -1,5 +1,9 @@
+use std::any::Any;
+use std::boxed::Box;
 use std::string::String;
-use std::time::{Duration, SystemTime};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
 
 use clap::Parser;
 use serde::Deserialize;
@@ -7,7 +11,28 @@ use serde::Deserialize;
 pub use simplelog::{LevelFilter, TermLogger, TerminalMode};
 
 pub mod error;
-pub use error::HalError;
+pub use error::{HalError, ResultExt};
+
+pub mod backend;
+pub use backend::{BackendOption, HalBackend, Registry};
+
+pub mod config;
+pub use config::{load_config, ConfigError, ConfigFormat};
+
+pub mod devices;
+pub use devices::{BusDeviceConfig, HalDevices, MultiDeviceConfig};
+
+pub mod pins;
+pub use pins::{PinBias, PinDrive, PinSpec};
+
+pub mod status;
+pub use status::{StatusConfig, StatusHal, StatusLeds};
+
+pub mod script;
+pub use script::{Script, ScriptError};
+
+pub mod registers;
+pub use registers::{RegisterError, RegisterMap, Registers};
 
 #[cfg(all(feature = "hal-linux", target_os = "linux"))]
 pub mod linux;
@@ -15,19 +40,60 @@ pub mod linux;
 #[cfg(feature = "hal-cp2130")]
 pub mod cp2130;
 
+#[cfg(feature = "hal-ftdi")]
+pub mod ftdi;
+
+#[cfg(feature = "hal-mcp2210")]
+pub mod mcp2210;
+
+#[cfg(feature = "mock")]
+pub mod mock;
+
+use embedded_hal::delay::DelayUs;
+use embedded_hal::digital::{InputPin, OutputPin};
+
 use crate::*;
 
 /// Generic device configuration structure for SPI drivers
 #[derive(Debug, Parser, Deserialize)]
 pub struct DeviceConfig {
+    /// HAL backend name (defaults to detection from device options)
+    #[clap(long, env = "HAL_BACKEND")]
+    pub backend: Option<String>,
+
+    /// Backend-specific options in `key=value` form
+    #[clap(long = "backend-opt", env = "HAL_BACKEND_OPTS", value_delimiter = ',')]
+    #[serde(default)]
+    pub options: Vec<BackendOption>,
+
     /// Linux SpiDev SPI device
     #[clap(long, group = "spi-kind", env = "SPI_DEV")]
     pub spi_dev: Option<String>,
 
-    /// CP2130 SPI device
+    /// CP2130 SPI device (index within matching devices)
     #[clap(long, group = "spi-kind", env = "CP2130_DEV")]
     pub cp2130_dev: Option<usize>,
 
+    /// CP2130 USB serial number filter
+    #[clap(long, env = "CP2130_SERIAL")]
+    pub cp2130_serial: Option<String>,
+
+    /// CP2130 USB path filter as BUS:ADDRESS
+    #[clap(long, env = "CP2130_PATH")]
+    pub cp2130_path: Option<String>,
+
+    /// CP2130 USB ID filter as VID:PID
+    #[clap(long, env = "CP2130_ID")]
+    pub cp2130_id: Option<String>,
+
+    /// FTDI MPSSE SPI device as VID:PID (empty for the default FT232H)
+    #[clap(long, group = "spi-kind", env = "FTDI_DEV")]
+    pub ftdi_dev: Option<String>,
+
+    /// MCP2210 hidraw device (eg. /dev/hidraw0)
+    #[clap(long, group = "spi-kind", env = "MCP2210_DEV")]
+    pub mcp2210_dev: Option<String>,
+
     #[clap(flatten)]
     #[serde(flatten)]
     pub spi: SpiConfig,
@@ -38,43 +104,60 @@ pub struct DeviceConfig {
 }
 
 /// SPI device configuration
-#[derive(Debug, Clone, Parser, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Parser, Deserialize)]
 pub struct SpiConfig {
     /// Baud rate setting
     #[clap(long = "spi-baud", default_value = "1000000", env = "SPI_BAUD")]
+    #[serde(default = "default_baud")]
     pub baud: u32,
 
     /// SPI mode setting
     #[clap(long = "spi-mode", default_value = "0", env = "SPI_MODE")]
+    #[serde(default)]
     pub mode: u32,
 }
 
-/// Pin configuration object
+/// Pin configuration object, see `PinSpec` for per-pin options
 #[derive(Debug, Clone, Parser, Deserialize)]
 pub struct PinConfig {
     /// Chip Select (output) pin
     #[clap(long = "cs-pin", default_value = "16", env = "CS_PIN")]
-    pub chip_select: u64,
+    #[serde(default = "default_cs_pin")]
+    pub chip_select: PinSpec,
 
     /// Reset (output) pin
     #[clap(long = "reset-pin", default_value = "17", env = "RESET_PIN")]
-    pub reset: u64,
+    #[serde(default = "default_reset_pin")]
+    pub reset: PinSpec,
 
     /// Busy (input) pin
     #[clap(long = "busy-pin", env = "BUSY_PIN")]
-    pub busy: Option<u64>,
+    pub busy: Option<PinSpec>,
 
     /// Ready (input) pin
     #[clap(long = "ready-pin", env = "READY_PIN")]
-    pub ready: Option<u64>,
+    pub ready: Option<PinSpec>,
 
     /// LED 0 (output) pin
     #[clap(long = "led0-pin", env = "LED0_PIN")]
-    pub led0: Option<u64>,
+    pub led0: Option<PinSpec>,
 
     /// LED 1 (output) pin
     #[clap(long = "led1-pin", env = "LED1_PIN")]
-    pub led1: Option<u64>,
+    pub led1: Option<PinSpec>,
+}
+
+// Config file defaults, matching the CLI defaults above
+fn default_baud() -> u32 {
+    1_000_000
+}
+
+fn default_cs_pin() -> PinSpec {
+    PinSpec::new(16)
+}
+
+fn default_reset_pin() -> PinSpec {
+    PinSpec::new(17)
 }
 
 /// Log configuration object
@@ -104,43 +187,140 @@ pub struct HalInst {
     pub pins: HalPins,
 }
 impl HalInst {
-    /// Load a hal instance from the provided configuration
+    /// Load a hal instance from the provided configuration using the default backends
     pub fn load(config: &DeviceConfig) -> Result<HalInst, HalError> {
-        // Process HAL configuration options
-        let hal = match (&config.spi_dev, &config.cp2130_dev) {
-            (Some(_), Some(_)) => {
-                error!("Only one of spi_dev and cp2130_dev may be specified");
-                return Err(HalError::InvalidConfig);
-            }
-            #[cfg(all(feature = "hal-linux", target_os = "linux"))]
-            (Some(s), None) => {
-                debug!("Creating linux hal driver");
-                linux::LinuxDriver::new(s, &config.spi, &config.pins)?
-            }
-            #[cfg(all(feature = "hal-linux", not(target_os = "linux")))]
-            (Some(s), None) => {
-                error!("Linux HAL only supported on linux platforms");
-                return Err(HalError::InvalidConfig);
-            }
-            #[cfg(feature = "hal-cp2130")]
-            (None, Some(i)) => {
-                debug!("Creating cp2130 hal driver");
-                cp2130::Cp2130Driver::new(*i, &config.spi, &config.pins)?
-            }
-            _ => {
-                error!("No SPI configuration provided or no matching implementation found");
-                return Err(HalError::InvalidConfig);
-            }
+        Self::load_with(&Registry::default(), config)
+    }
+
+    /// Load a hal instance from the provided configuration using the provided backend registry
+    pub fn load_with(registry: &Registry, config: &DeviceConfig) -> Result<HalInst, HalError> {
+        registry.load(config)
+    }
+
+    /// Convert this instance to use a shared SPI bus, allowing further devices
+    /// with separate chip selects to be attached via `HalInst::shared_bus`
+    pub fn into_shared(self) -> HalInst {
+        let spi = match self.spi {
+            HalSpi::Shared(s) => HalSpi::Shared(s),
+            s => HalSpi::Shared(Arc::new(Mutex::new(s))),
         };
 
-        Ok(hal)
+        HalInst {
+            base: self.base,
+            spi,
+            pins: self.pins,
+        }
+    }
+
+    /// Fetch the shared SPI bus for this instance, if shared
+    pub fn shared_bus(&self) -> Option<HalSpi> {
+        match &self.spi {
+            HalSpi::Shared(s) => Some(HalSpi::Shared(s.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// ManagedChipSelect indicates HalInst controls the CS line
+impl ManagedChipSelect for HalInst {}
+
+impl embedded_hal::spi::ErrorType for HalInst {
+    type Error = HalError;
+}
+
+/// SPI implementation for HalInst, managing the CS pin
+impl embedded_hal::spi::SpiDevice<u8> for HalInst {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        // Shared buses are held for the duration of the transaction
+        // so other devices cannot transfer while CS is asserted
+        match &mut self.spi {
+            HalSpi::Shared(s) => {
+                let mut spi = lock_shared(s);
+                cs_transaction(&mut self.pins.cs, &mut *spi, operations)
+            }
+            spi => cs_transaction(&mut self.pins.cs, spi, operations),
+        }
+    }
+}
+
+/// Lock a shared bus, recovering from poisoning as a panic in another device's
+/// transaction does not invalidate the underlying bus
+fn lock_shared(spi: &Mutex<HalSpi>) -> MutexGuard<'_, HalSpi> {
+    spi.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Execute a transaction with the provided chip select asserted
+fn cs_transaction(
+    cs: &mut HalOutputPin,
+    spi: &mut HalSpi,
+    operations: &mut [Operation<'_, u8>],
+) -> Result<(), HalError> {
+    cs.set_low()?;
+
+    let r = spi.transaction(operations);
+
+    cs.set_high()?;
+
+    r
+}
+
+/// Reset pin implementation for HalInst
+impl Reset for HalInst {
+    type Error = HalError;
+
+    /// Set the reset pin state
+    fn set_reset(&mut self, state: PinState) -> Result<(), Self::Error> {
+        match state {
+            PinState::High => self.pins.reset.set_high(),
+            PinState::Low => self.pins.reset.set_low(),
+        }
+    }
+}
+
+/// Busy pin implementation for HalInst
+impl Busy for HalInst {
+    type Error = HalError;
+
+    /// Fetch the busy pin state
+    fn get_busy(&mut self) -> Result<PinState, Self::Error> {
+        match self.pins.busy.is_high()? {
+            true => Ok(PinState::High),
+            false => Ok(PinState::Low),
+        }
+    }
+}
+
+/// Ready pin implementation for HalInst
+impl Ready for HalInst {
+    type Error = HalError;
+
+    /// Fetch the ready pin state
+    fn get_ready(&mut self) -> Result<PinState, Self::Error> {
+        match self.pins.ready.is_high()? {
+            true => Ok(PinState::High),
+            false => Ok(PinState::Low),
+        }
+    }
+}
+
+impl embedded_hal::delay::DelayUs for HalInst {
+    fn delay_us(&mut self, us: u32) {
+        HalDelay::new().delay_us(us)
     }
 }
 
 /// Base storage for Hal instances
 pub enum HalBase {
+    #[cfg(all(feature = "hal-linux", target_os = "linux"))]
+    Linux(std::sync::Arc<std::sync::Mutex<gpio_cdev::Chip>>),
     #[cfg(feature = "hal-cp2130")]
     Cp2130(driver_cp2130::Cp2130),
+    #[cfg(feature = "hal-ftdi")]
+    Ftdi(ftdi_embedded_hal::FtHal<ftdi::Device>),
+    #[cfg(feature = "hal-mcp2210")]
+    Mcp2210(std::sync::Arc<std::sync::Mutex<mcp2210::Mcp2210>>),
+    /// Base storage for externally provided backends
+    Dyn(Box<dyn Any + Send>),
     None,
 }
 
@@ -151,6 +331,14 @@ pub enum HalSpi {
     Linux(linux_embedded_hal::Spidev),
     #[cfg(feature = "hal-cp2130")]
     Cp2130(driver_cp2130::Spi),
+    #[cfg(feature = "hal-ftdi")]
+    Ftdi(ftdi::FtdiSpi),
+    #[cfg(feature = "hal-mcp2210")]
+    Mcp2210(mcp2210::Mcp2210Spi),
+    /// SPI device for externally provided backends
+    Dyn(Box<dyn SpiDevice<u8, Error = HalError> + Send>),
+    /// SPI bus shared between devices with separate chip selects
+    Shared(Arc<Mutex<HalSpi>>),
 }
 
 impl embedded_hal::spi::SpiDevice<u8> for HalSpi {
@@ -160,6 +348,12 @@ impl embedded_hal::spi::SpiDevice<u8> for HalSpi {
             HalSpi::Linux(i) => i.transaction(operations)?,
             #[cfg(feature = "hal-cp2130")]
             HalSpi::Cp2130(i) => i.transaction(operations)?,
+            #[cfg(feature = "hal-ftdi")]
+            HalSpi::Ftdi(i) => i.transaction(operations)?,
+            #[cfg(feature = "hal-mcp2210")]
+            HalSpi::Mcp2210(i) => i.transaction(operations)?,
+            HalSpi::Dyn(i) => i.transaction(operations)?,
+            HalSpi::Shared(i) => lock_shared(i).transaction(operations)?,
             #[allow(unreachable_patterns)]
             _ => return Err(HalError::NoDriver),
         }
@@ -172,6 +366,12 @@ impl embedded_hal::spi::SpiDevice<u8> for HalSpi {
             HalSpi::Linux(i) => i.write(data)?,
             #[cfg(feature = "hal-cp2130")]
             HalSpi::Cp2130(i) => i.write(data)?,
+            #[cfg(feature = "hal-ftdi")]
+            HalSpi::Ftdi(i) => i.write(data)?,
+            #[cfg(feature = "hal-mcp2210")]
+            HalSpi::Mcp2210(i) => i.write(data)?,
+            HalSpi::Dyn(i) => i.write(data)?,
+            HalSpi::Shared(i) => lock_shared(i).write(data)?,
             #[allow(unreachable_patterns)]
             _ => return Err(HalError::NoDriver),
         }
@@ -184,6 +384,12 @@ impl embedded_hal::spi::SpiDevice<u8> for HalSpi {
             HalSpi::Linux(i) => i.transfer(buff, data)?,
             #[cfg(feature = "hal-cp2130")]
             HalSpi::Cp2130(i) => i.transfer(buff, data)?,
+            #[cfg(feature = "hal-ftdi")]
+            HalSpi::Ftdi(i) => i.transfer(buff, data)?,
+            #[cfg(feature = "hal-mcp2210")]
+            HalSpi::Mcp2210(i) => i.transfer(buff, data)?,
+            HalSpi::Dyn(i) => i.transfer(buff, data)?,
+            HalSpi::Shared(i) => lock_shared(i).transfer(buff, data)?,
             #[allow(unreachable_patterns)]
             _ => return Err(HalError::NoDriver),
         }
@@ -196,6 +402,12 @@ impl embedded_hal::spi::SpiDevice<u8> for HalSpi {
             HalSpi::Linux(i) => i.transfer_in_place(data)?,
             #[cfg(feature = "hal-cp2130")]
             HalSpi::Cp2130(i) => i.transfer_in_place(data)?,
+            #[cfg(feature = "hal-ftdi")]
+            HalSpi::Ftdi(i) => i.transfer_in_place(data)?,
+            #[cfg(feature = "hal-mcp2210")]
+            HalSpi::Mcp2210(i) => i.transfer_in_place(data)?,
+            HalSpi::Dyn(i) => i.transfer_in_place(data)?,
+            HalSpi::Shared(i) => lock_shared(i).transfer_in_place(data)?,
             #[allow(unreachable_patterns)]
             _ => return Err(HalError::NoDriver),
         }
@@ -211,9 +423,17 @@ impl embedded_hal::spi::ErrorType for HalSpi {
 #[non_exhaustive]
 pub enum HalInputPin {
     #[cfg(all(feature = "hal-linux", target_os = "linux"))]
-    Linux(linux_embedded_hal::SysfsPin),
+    Linux(linux::LinuxInputPin),
     #[cfg(feature = "hal-cp2130")]
     Cp2130(driver_cp2130::InputPin),
+    #[cfg(feature = "hal-ftdi")]
+    Ftdi(ftdi_embedded_hal::InputPin<ftdi::Device>),
+    #[cfg(feature = "hal-mcp2210")]
+    Mcp2210(mcp2210::Mcp2210Pin),
+    /// Input pin for externally provided backends
+    Dyn(Box<dyn embedded_hal::digital::InputPin<Error = HalError> + Send>),
+    /// Software inverted (active-low) input pin
+    Inverted(Box<HalInputPin>),
     None,
 }
 
@@ -226,6 +446,16 @@ impl embedded_hal::digital::InputPin for HalInputPin {
             #[cfg(feature = "hal-cp2130")]
             HalInputPin::Cp2130(i) => i.is_high()?,
 
+            #[cfg(feature = "hal-ftdi")]
+            HalInputPin::Ftdi(i) => i.is_high()?,
+
+            #[cfg(feature = "hal-mcp2210")]
+            HalInputPin::Mcp2210(i) => i.is_high()?,
+
+            HalInputPin::Dyn(i) => i.is_high()?,
+
+            HalInputPin::Inverted(i) => !i.is_high()?,
+
             #[allow(unreachable_patterns)]
             _ => return Err(HalError::NoPin),
         };
@@ -246,9 +476,17 @@ impl embedded_hal::digital::ErrorType for HalInputPin {
 #[non_exhaustive]
 pub enum HalOutputPin {
     #[cfg(all(feature = "hal-linux", target_os = "linux"))]
-    Linux(linux_embedded_hal::SysfsPin),
+    Linux(linux::LinuxOutputPin),
     #[cfg(feature = "hal-cp2130")]
     Cp2130(driver_cp2130::OutputPin),
+    #[cfg(feature = "hal-ftdi")]
+    Ftdi(ftdi_embedded_hal::OutputPin<ftdi::Device>),
+    #[cfg(feature = "hal-mcp2210")]
+    Mcp2210(mcp2210::Mcp2210Pin),
+    /// Output pin for externally provided backends
+    Dyn(Box<dyn embedded_hal::digital::OutputPin<Error = HalError> + Send>),
+    /// Software inverted (active-low) output pin
+    Inverted(Box<HalOutputPin>),
     None,
 }
 
@@ -261,6 +499,16 @@ impl embedded_hal::digital::OutputPin for HalOutputPin {
             #[cfg(feature = "hal-cp2130")]
             HalOutputPin::Cp2130(i) => i.set_high()?,
 
+            #[cfg(feature = "hal-ftdi")]
+            HalOutputPin::Ftdi(i) => i.set_high()?,
+
+            #[cfg(feature = "hal-mcp2210")]
+            HalOutputPin::Mcp2210(i) => i.set_high()?,
+
+            HalOutputPin::Dyn(i) => i.set_high()?,
+
+            HalOutputPin::Inverted(i) => i.set_low()?,
+
             #[allow(unreachable_patterns)]
             _ => return Err(HalError::NoPin),
         }
@@ -275,6 +523,16 @@ impl embedded_hal::digital::OutputPin for HalOutputPin {
             #[cfg(feature = "hal-cp2130")]
             HalOutputPin::Cp2130(i) => i.set_low()?,
 
+            #[cfg(feature = "hal-ftdi")]
+            HalOutputPin::Ftdi(i) => i.set_low()?,
+
+            #[cfg(feature = "hal-mcp2210")]
+            HalOutputPin::Mcp2210(i) => i.set_low()?,
+
+            HalOutputPin::Dyn(i) => i.set_low()?,
+
+            HalOutputPin::Inverted(i) => i.set_high()?,
+
             #[allow(unreachable_patterns)]
             _ => return Err(HalError::NoPin),
         }
@@ -286,15 +544,6 @@ impl embedded_hal::digital::ErrorType for HalOutputPin {
     type Error = HalError;
 }
 
-/// Load a configuration file
-pub fn load_config<T>(file: &str) -> T
-where
-    T: serde::de::DeserializeOwned,
-{
-    let d = std::fs::read_to_string(file).expect("error reading file");
-    toml::from_str(&d).expect("error parsing toml file")
-}
-
 /// HalPins object for conveniently returning bound pins
 pub struct HalPins {
     pub cs: HalOutputPin,
@@ -305,13 +554,184 @@ pub struct HalPins {
     pub led1: HalOutputPin,
 }
 
-/// HalDelay object based on blocking SystemTime::elapsed calls
-pub struct HalDelay;
+/// Default `HalDelay` spin threshold
+pub const DEFAULT_SPIN_THRESHOLD: Duration = Duration::from_micros(100);
+
+/// HalDelay object using a monotonic clock, sleeping for the bulk of a delay
+/// then spinning for the remainder below `spin_threshold`.
+///
+/// Larger thresholds improve precision at the cost of CPU time, a zero threshold
+/// only sleeps (with precision limited by the OS scheduler).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HalDelay {
+    pub spin_threshold: Duration,
+}
+
+impl HalDelay {
+    /// Create a delay with the default spin threshold
+    pub const fn new() -> Self {
+        Self {
+            spin_threshold: DEFAULT_SPIN_THRESHOLD,
+        }
+    }
+
+    /// Create a delay with the provided spin threshold
+    pub const fn with_spin_threshold(spin_threshold: Duration) -> Self {
+        Self { spin_threshold }
+    }
+
+    /// Block for the provided duration
+    pub fn delay(&self, d: Duration) {
+        let deadline = Instant::now() + d;
+
+        if d > self.spin_threshold {
+            std::thread::sleep(d - self.spin_threshold);
+        }
+
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Block for the provided number of nanoseconds
+    pub fn delay_ns(&mut self, ns: u32) {
+        self.delay(Duration::from_nanos(ns as u64))
+    }
+}
+
+impl Default for HalDelay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl embedded_hal::delay::DelayUs for HalDelay {
     fn delay_us(&mut self, us: u32) {
-        let n = SystemTime::now();
-        let d = Duration::from_micros(us as u64);
-        while n.elapsed().unwrap() < d {}
+        self.delay(Duration::from_micros(us as u64))
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay(Duration::from_millis(ms as u64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use std::vec;
+
+    use super::*;
+
+    /// SPI device discarding all operations for HalInst tests
+    struct NullSpi;
+
+    impl embedded_hal::spi::SpiDevice<u8> for NullSpi {
+        fn transaction(&mut self, _ops: &mut [Operation<'_, u8>]) -> Result<(), HalError> {
+            Ok(())
+        }
+    }
+
+    impl embedded_hal::spi::ErrorType for NullSpi {
+        type Error = HalError;
+    }
+
+    /// Output pin recording the set levels
+    struct Recorder(Arc<Mutex<Vec<bool>>>);
+
+    impl embedded_hal::digital::OutputPin for Recorder {
+        fn set_high(&mut self) -> Result<(), HalError> {
+            self.0.lock().unwrap().push(true);
+            Ok(())
+        }
+
+        fn set_low(&mut self) -> Result<(), HalError> {
+            self.0.lock().unwrap().push(false);
+            Ok(())
+        }
+    }
+
+    impl embedded_hal::digital::ErrorType for Recorder {
+        type Error = HalError;
+    }
+
+    fn assert_hal<H: Hal<HalError>>(_h: &H) {}
+
+    #[test]
+    fn test_hal_delay() {
+        for mut d in [
+            HalDelay::new(),
+            HalDelay::with_spin_threshold(Duration::from_secs(0)),
+            HalDelay::with_spin_threshold(Duration::from_secs(1)),
+        ] {
+            let n = Instant::now();
+            d.delay_us(1_500);
+            assert!(n.elapsed() >= Duration::from_micros(1_500));
+
+            let n = Instant::now();
+            d.delay_ms(2);
+            assert!(n.elapsed() >= Duration::from_millis(2));
+
+            let n = Instant::now();
+            d.delay_ns(500);
+            assert!(n.elapsed() >= Duration::from_nanos(500));
+        }
+    }
+
+    #[test]
+    fn test_hal_inst() {
+        let cs = Arc::new(Mutex::new(vec![]));
+
+        let mut h = HalInst {
+            base: HalBase::None,
+            spi: HalSpi::Dyn(Box::new(NullSpi)),
+            pins: HalPins {
+                cs: HalOutputPin::Dyn(Box::new(Recorder(cs.clone()))),
+                reset: HalOutputPin::None,
+                busy: HalInputPin::None,
+                ready: HalInputPin::None,
+                led0: HalOutputPin::None,
+                led1: HalOutputPin::None,
+            },
+        };
+
+        assert_hal(&h);
+
+        h.prefix_write(&[0xAA], &[0xBB, 0xCC]).unwrap();
+        assert_eq!(*cs.lock().unwrap(), vec![false, true]);
+
+        assert!(h.set_reset(PinState::High).unwrap_err().is_no_pin());
+        assert!(h.get_busy().unwrap_err().is_no_pin());
+    }
+
+    #[test]
+    fn test_shared_poisoned() {
+        let bus = Arc::new(Mutex::new(HalSpi::Dyn(Box::new(NullSpi))));
+
+        let b = bus.clone();
+        let _ = std::thread::spawn(move || {
+            let _l = b.lock().unwrap();
+            panic!("transaction failed");
+        })
+        .join();
+        assert!(bus.is_poisoned());
+
+        let cs = Arc::new(Mutex::new(vec![]));
+        let mut h = HalInst {
+            base: HalBase::None,
+            spi: HalSpi::Shared(bus),
+            pins: HalPins {
+                cs: HalOutputPin::Dyn(Box::new(Recorder(cs.clone()))),
+                reset: HalOutputPin::None,
+                busy: HalInputPin::None,
+                ready: HalInputPin::None,
+                led0: HalOutputPin::None,
+                led1: HalOutputPin::None,
+            },
+        };
+
+        h.prefix_write(&[0xAA], &[0xBB, 0xCC]).unwrap();
+        assert_eq!(*cs.lock().unwrap(), vec![false, true]);
+
+        h.spi.write(&[0xAA]).unwrap();
     }
 }
@@ -0,0 +1,390 @@
+//! Pin specifications
+//!
+//! Pins may be configured as a bare index (`17`), a string of comma separated flags
+//! (`"17,active-low,pull-up,open-drain,initial=high,label=radio-irq"`) or a table
+//! with `index`, `active_low`, `bias`, `drive`, `initial` and `label` fields.
+//!
+//! Active-low pins are inverted so `Busy` / `Ready` and the output pins operate on the
+//! logical rather than electrical state, initial levels are also logical.
+
+use std::boxed::Box;
+use std::str::FromStr;
+use std::string::{String, ToString};
+use std::time::{Duration, Instant};
+
+use embedded_hal::digital::{InputPin, OutputPin};
+use serde::Deserialize;
+
+use super::{HalError, HalInputPin, HalOutputPin};
+use crate::{Edge, PinState, WaitForEdge};
+
+/// Interval between pin reads when waiting for edges without interrupt support
+pub const EDGE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Pin bias (internal pull resistor) setting
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PinBias {
+    PullUp,
+    PullDown,
+}
+
+/// Output pin drive mode
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PinDrive {
+    PushPull,
+    OpenDrain,
+}
+
+/// Pin specification, an index with optional electrical configuration
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "PinSpecRepr")]
+pub struct PinSpec {
+    /// Backend-specific pin index
+    pub index: u64,
+    /// Invert the pin so logical high is electrically low
+    pub active_low: bool,
+    /// Pull resistor setting, `None` to leave as configured
+    pub bias: Option<PinBias>,
+    /// Output drive mode, `None` for the backend default
+    pub drive: Option<PinDrive>,
+    /// Initial logical output level, `None` for the backend default
+    pub initial: Option<PinState>,
+    /// Human readable label for logging and errors
+    pub label: Option<String>,
+}
+
+impl PinSpec {
+    /// Create a pin specification with the provided index and default settings
+    pub fn new(index: u64) -> Self {
+        Self {
+            index,
+            active_low: false,
+            bias: None,
+            drive: None,
+            initial: None,
+            label: None,
+        }
+    }
+
+    /// Resolve the electrical level for a logical level, applying active-low inversion
+    pub fn electrical(&self, level: &PinState) -> bool {
+        (*level == PinState::High) != self.active_low
+    }
+
+    /// Check the settings used by this pin are supported by a backend,
+    /// returning an `InvalidConfig` error otherwise
+    pub fn check_support(&self, backend: &str, bias: bool, drive: bool) -> Result<(), HalError> {
+        let unsupported = match (self.bias, self.drive) {
+            (Some(b), _) if !bias => format!("bias {:?}", b),
+            (_, Some(d)) if !drive => format!("drive {:?}", d),
+            _ => return Ok(()),
+        };
+
+        Err(HalError::InvalidConfig(format!(
+            "Pin {} {} is not supported by the {} backend",
+            self, unsupported, backend
+        )))
+    }
+}
+
+impl From<u64> for PinSpec {
+    fn from(index: u64) -> Self {
+        Self::new(index)
+    }
+}
+
+/// Display the pin index with the label where specified
+impl std::fmt::Display for PinSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.label {
+            Some(l) => write!(f, "{} ({})", self.index, l),
+            None => write!(f, "{}", self.index),
+        }
+    }
+}
+
+/// Parse a pin specification from an index followed by comma separated flags
+impl FromStr for PinSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(|p| p.trim());
+
+        let index = parts.next().unwrap_or_default();
+        let mut spec = match index.parse() {
+            Ok(i) => PinSpec::new(i),
+            Err(_) => return Err(format!("invalid pin index '{}'", index)),
+        };
+
+        for p in parts {
+            match p.split_once('=') {
+                Some(("initial", v)) => spec.initial = Some(parse_level(v)?),
+                Some(("label", v)) => spec.label = Some(v.to_string()),
+                Some(("bias", v)) => spec.bias = Some(parse_bias(v)?),
+                Some(("drive", v)) => spec.drive = Some(parse_drive(v)?),
+                Some(_) => return Err(format!("unrecognised pin option '{}'", p)),
+                None if p == "active-low" => spec.active_low = true,
+                None if p == "active-high" => spec.active_low = false,
+                None => match (parse_bias(p), parse_drive(p)) {
+                    (Ok(b), _) => spec.bias = Some(b),
+                    (_, Ok(d)) => spec.drive = Some(d),
+                    _ => return Err(format!("unrecognised pin flag '{}'", p)),
+                },
+            }
+        }
+
+        Ok(spec)
+    }
+}
+
+fn parse_level(s: &str) -> Result<PinState, String> {
+    match s {
+        "high" | "1" => Ok(PinState::High),
+        "low" | "0" => Ok(PinState::Low),
+        _ => Err(format!("invalid pin level '{}', expected high or low", s)),
+    }
+}
+
+fn parse_bias(s: &str) -> Result<PinBias, String> {
+    match s {
+        "pull-up" => Ok(PinBias::PullUp),
+        "pull-down" => Ok(PinBias::PullDown),
+        _ => Err(format!("invalid pin bias '{}'", s)),
+    }
+}
+
+fn parse_drive(s: &str) -> Result<PinDrive, String> {
+    match s {
+        "push-pull" => Ok(PinDrive::PushPull),
+        "open-drain" => Ok(PinDrive::OpenDrain),
+        _ => Err(format!("invalid pin drive '{}'", s)),
+    }
+}
+
+/// Serialised pin specification, as an index, flag string or table
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PinSpecRepr {
+    Index(u64),
+    Flags(String),
+    Table {
+        index: u64,
+        #[serde(default)]
+        active_low: bool,
+        bias: Option<PinBias>,
+        drive: Option<PinDrive>,
+        initial: Option<String>,
+        label: Option<String>,
+    },
+}
+
+impl std::convert::TryFrom<PinSpecRepr> for PinSpec {
+    type Error = String;
+
+    fn try_from(r: PinSpecRepr) -> Result<Self, Self::Error> {
+        match r {
+            PinSpecRepr::Index(i) => Ok(PinSpec::new(i)),
+            PinSpecRepr::Flags(s) => s.parse(),
+            PinSpecRepr::Table {
+                index,
+                active_low,
+                bias,
+                drive,
+                initial,
+                label,
+            } => Ok(PinSpec {
+                index,
+                active_low,
+                bias,
+                drive,
+                initial: initial.as_deref().map(parse_level).transpose()?,
+                label,
+            }),
+        }
+    }
+}
+
+impl HalInputPin {
+    /// Apply software active-low inversion where specified
+    pub fn with_active_low(self, active_low: bool) -> Self {
+        match active_low {
+            true => HalInputPin::Inverted(Box::new(self)),
+            false => self,
+        }
+    }
+}
+
+/// Edge waiting, using interrupts where supported by the backend
+/// (linux GPIO character device) and polling at `EDGE_POLL_INTERVAL` otherwise
+impl WaitForEdge for HalInputPin {
+    type Error = HalError;
+
+    fn wait_for_edge(&mut self, edge: Edge, timeout: Duration) -> Result<bool, Self::Error> {
+        match self {
+            #[cfg(all(feature = "hal-linux", target_os = "linux"))]
+            HalInputPin::Linux(p) => p.wait_for_edge(edge, timeout),
+            HalInputPin::None => Err(HalError::NoPin),
+            _ => poll_for_edge(self, edge, timeout, EDGE_POLL_INTERVAL),
+        }
+    }
+}
+
+/// Wait for an edge by polling the pin state at the provided interval,
+/// returning `true` if the edge occurred and `false` on timeout
+pub fn poll_for_edge<P: InputPin>(
+    pin: &P,
+    edge: Edge,
+    timeout: Duration,
+    interval: Duration,
+) -> Result<bool, P::Error> {
+    let start = Instant::now();
+    let mut last = pin.is_high()?;
+
+    while start.elapsed() < timeout {
+        std::thread::sleep(interval);
+
+        let now = pin.is_high()?;
+
+        match (edge, last, now) {
+            (Edge::Rising, false, true) => return Ok(true),
+            (Edge::Falling, true, false) => return Ok(true),
+            (Edge::Both, a, b) if a != b => return Ok(true),
+            _ => (),
+        }
+
+        last = now;
+    }
+
+    Ok(false)
+}
+
+impl HalOutputPin {
+    /// Apply software active-low inversion where specified
+    pub fn with_active_low(self, active_low: bool) -> Self {
+        match active_low {
+            true => HalOutputPin::Inverted(Box::new(self)),
+            false => self,
+        }
+    }
+
+    /// Apply software active-low inversion and set the initial logical level from a pin
+    /// specification, for backends without native support
+    pub fn configure(self, spec: &PinSpec, default: Option<PinState>) -> Result<Self, HalError> {
+        let mut p = self.with_active_low(spec.active_low);
+
+        match spec.initial.clone().or(default) {
+            Some(PinState::High) => p.set_high()?,
+            Some(PinState::Low) => p.set_low()?,
+            None => (),
+        }
+
+        Ok(p)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_pin_spec() {
+        assert_eq!("17".parse::<PinSpec>().unwrap(), PinSpec::new(17));
+
+        let p: PinSpec = "4, active-low, pull-up, open-drain, initial=high, label=irq"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            p,
+            PinSpec {
+                index: 4,
+                active_low: true,
+                bias: Some(PinBias::PullUp),
+                drive: Some(PinDrive::OpenDrain),
+                initial: Some(PinState::High),
+                label: Some("irq".to_string()),
+            }
+        );
+        assert_eq!(p.to_string(), "4 (irq)");
+        assert!(!p.electrical(&PinState::High));
+
+        assert!("x".parse::<PinSpec>().is_err());
+        assert!("4,sideways".parse::<PinSpec>().is_err());
+        assert!("4,initial=maybe".parse::<PinSpec>().is_err());
+    }
+
+    #[derive(Deserialize)]
+    struct Pins {
+        a: PinSpec,
+        b: PinSpec,
+        c: PinSpec,
+    }
+
+    #[test]
+    fn test_deserialize_pin_spec() {
+        let p: Pins = toml::from_str(
+            r#"
+            a = 3
+            b = "5,active-low"
+            c = { index = 7, bias = "pull-down", initial = "low", label = "reset" }
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(p.a, PinSpec::new(3));
+        assert!(p.b.active_low);
+        assert_eq!(p.c.bias, Some(PinBias::PullDown));
+        assert_eq!(p.c.initial, Some(PinState::Low));
+        assert_eq!(p.c.label.as_deref(), Some("reset"));
+    }
+
+    /// Input pin toggling after a number of reads
+    struct Toggle(std::sync::atomic::AtomicUsize, usize);
+
+    impl InputPin for Toggle {
+        fn is_high(&self) -> Result<bool, HalError> {
+            let n = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(n >= self.1)
+        }
+
+        fn is_low(&self) -> Result<bool, HalError> {
+            Ok(!self.is_high()?)
+        }
+    }
+
+    impl embedded_hal::digital::ErrorType for Toggle {
+        type Error = HalError;
+    }
+
+    #[test]
+    fn test_poll_for_edge() {
+        let interval = Duration::from_micros(10);
+        let timeout = Duration::from_millis(100);
+
+        let p = Toggle(Default::default(), 3);
+        assert!(poll_for_edge(&p, Edge::Rising, timeout, interval).unwrap());
+
+        let p = Toggle(Default::default(), 3);
+        assert!(!poll_for_edge(&p, Edge::Falling, timeout, interval).unwrap());
+
+        let mut p = HalInputPin::Inverted(Box::new(HalInputPin::Dyn(Box::new(Toggle(
+            Default::default(),
+            3,
+        )))));
+        assert!(p.wait_for_edge(Edge::Falling, timeout).unwrap());
+
+        let mut p = HalInputPin::None;
+        assert!(p
+            .wait_for_edge(Edge::Both, timeout)
+            .unwrap_err()
+            .is_no_pin());
+    }
+
+    #[test]
+    fn test_check_support() {
+        let p: PinSpec = "4,pull-up".parse().unwrap();
+        assert!(p.check_support("test", true, false).is_ok());
+        assert!(p.check_support("test", false, true).is_err());
+    }
+}
@@ -0,0 +1,790 @@
+//! Register map descriptions
+//!
+//! Register maps list the registers of a device (name, address, width in bits, access
+//! and fields) and the protocol used to access them, and may be TOML, JSON or YAML as with
+//! device configurations. SVD-style field names (`addressOffset`, `size`, `bitOffset`,
+//! `bitWidth`) are accepted as aliases so converted SVD descriptions may be used directly.
+//!
+//! ```toml
+//! address_width = 1   # address prefix bytes
+//! read_mask = 0x80    # set in the address for reads
+//!
+//! [[register]]
+//! name = "STATUS"
+//! address = 0x01
+//! access = "ro"
+//! fields = [
+//!     { name = "READY", offset = 0 },
+//!     { name = "MODE", offset = 1, width = 2, values = { sleep = 0, standby = 1, rx = 2 } },
+//! ]
+//! ```
+//!
+//! `Registers` combines a map with a `PrefixRead` / `PrefixWrite` implementation to read
+//! and write registers and fields by name, with `Decoded` values for display.
+
+use std::collections::BTreeMap;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::config::{load_config, parse_config, ConfigFormat};
+use super::HalError;
+use crate::{PrefixRead, PrefixWrite};
+
+/// Register map
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RegisterMap {
+    /// Address prefix length in bytes
+    #[serde(default = "default_address_width")]
+    pub address_width: usize,
+    /// Mask set in the address prefix for reads
+    #[serde(default, deserialize_with = "int")]
+    pub read_mask: u64,
+    /// Mask set in the address prefix for writes
+    #[serde(default, deserialize_with = "int")]
+    pub write_mask: u64,
+    /// Byte order of multi-byte register values
+    #[serde(default)]
+    pub endian: Endian,
+    /// Registers
+    #[serde(rename = "register", alias = "registers", default)]
+    pub registers: Vec<Register>,
+}
+
+fn default_address_width() -> usize {
+    1
+}
+
+/// Register value byte order
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+impl Default for Endian {
+    fn default() -> Self {
+        Endian::Big
+    }
+}
+
+/// Register access
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Access {
+    #[serde(rename = "ro", alias = "read-only")]
+    ReadOnly,
+    #[serde(rename = "wo", alias = "write-only")]
+    WriteOnly,
+    #[serde(rename = "rw", alias = "read-write")]
+    ReadWrite,
+}
+
+impl Access {
+    /// Check whether reads are permitted
+    pub fn readable(&self) -> bool {
+        *self != Access::WriteOnly
+    }
+
+    /// Check whether writes are permitted
+    pub fn writable(&self) -> bool {
+        *self != Access::ReadOnly
+    }
+}
+
+impl Default for Access {
+    fn default() -> Self {
+        Access::ReadWrite
+    }
+}
+
+/// Register description
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Register {
+    pub name: String,
+    /// Register address
+    #[serde(alias = "addressOffset", deserialize_with = "int")]
+    pub address: u64,
+    /// Register width in bits, a multiple of 8 up to 64
+    #[serde(alias = "size", default = "default_register_width")]
+    pub width: u32,
+    #[serde(default)]
+    pub access: Access,
+    /// Reset value, used for the other bits in field writes to write-only registers
+    #[serde(alias = "resetValue", default, deserialize_with = "int")]
+    pub reset: u64,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+}
+
+fn default_register_width() -> u32 {
+    8
+}
+
+/// Register field description
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Field {
+    pub name: String,
+    /// Least significant bit of the field
+    #[serde(alias = "bitOffset")]
+    pub offset: u32,
+    /// Field width in bits
+    #[serde(alias = "bitWidth", default = "default_field_width")]
+    pub width: u32,
+    /// Field access, defaulting to the register access
+    pub access: Option<Access>,
+    pub description: Option<String>,
+    /// Named field values
+    #[serde(default)]
+    pub values: BTreeMap<String, u64>,
+}
+
+fn default_field_width() -> u32 {
+    1
+}
+
+/// Deserialize an integer from a number or a (`0x` prefixed hex) string
+fn int<'de, D: Deserializer<'de>>(d: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum IntRepr {
+        Int(u64),
+        Str(String),
+    }
+
+    match IntRepr::deserialize(d)? {
+        IntRepr::Int(v) => Ok(v),
+        IntRepr::Str(s) => parse_int(&s).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Parse an integer with an optional `0x` (hex) or `0b` (binary) prefix
+pub fn parse_int(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+
+    let r = match (s.get(..2), s.get(2..)) {
+        (Some("0x"), Some(v)) | (Some("0X"), Some(v)) => u64::from_str_radix(v, 16),
+        (Some("0b"), Some(v)) | (Some("0B"), Some(v)) => u64::from_str_radix(v, 2),
+        _ => s.parse(),
+    };
+
+    r.map_err(|_| format!("invalid integer '{}'", s))
+}
+
+/// Bit mask for a field of the provided width
+fn mask(width: u32) -> u64 {
+    match width {
+        64 => u64::MAX,
+        w => (1 << w) - 1,
+    }
+}
+
+impl Field {
+    /// Extract the field value from a register value
+    pub fn extract(&self, value: u64) -> u64 {
+        (value >> self.offset) & mask(self.width)
+    }
+
+    /// Insert a field value into a register value
+    pub fn insert(&self, reg: u64, value: u64) -> u64 {
+        let m = mask(self.width) << self.offset;
+        (reg & !m) | ((value << self.offset) & m)
+    }
+
+    /// Lookup the name for a field value
+    pub fn value_name(&self, value: u64) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(_k, v)| **v == value)
+            .map(|(k, _v)| k.as_str())
+    }
+
+    /// Parse a field value by name or as an integer
+    pub fn parse_value(&self, s: &str) -> Result<u64, String> {
+        match self.values.get(s) {
+            Some(v) => Ok(*v),
+            None => parse_int(s),
+        }
+    }
+}
+
+impl Register {
+    /// Register width in bytes
+    pub fn len(&self) -> usize {
+        self.width as usize / 8
+    }
+
+    /// Check whether the register has zero width
+    pub fn is_empty(&self) -> bool {
+        self.width == 0
+    }
+
+    /// Fetch a field by name (ignoring case)
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Check register and field widths are valid
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width == 0 || self.width % 8 != 0 || self.width > 64 {
+            return Err(format!(
+                "width {} must be a multiple of 8 up to 64",
+                self.width
+            ));
+        }
+
+        if self.reset & !mask(self.width) != 0 {
+            return Err(format!(
+                "reset value 0x{:x} does not fit in {} bits",
+                self.reset, self.width
+            ));
+        }
+
+        for f in &self.fields {
+            let end = f.offset.checked_add(f.width);
+            if f.width == 0 || end.map_or(true, |e| e > self.width) {
+                return Err(format!(
+                    "field {} (offset {}, width {}) does not fit in {} bits",
+                    f.name, f.offset, f.width, self.width
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decode a register value
+    pub fn decode(&self, value: u64) -> Decoded {
+        let fields = self
+            .fields
+            .iter()
+            .map(|f| {
+                let v = f.extract(value);
+                DecodedField {
+                    name: f.name.clone(),
+                    offset: f.offset,
+                    width: f.width,
+                    value: v,
+                    label: f.value_name(v).map(|s| s.to_string()),
+                }
+            })
+            .collect();
+
+        Decoded {
+            name: self.name.clone(),
+            address: self.address,
+            width: self.width,
+            value,
+            fields,
+        }
+    }
+}
+
+/// Decoded register value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Decoded {
+    pub name: String,
+    pub address: u64,
+    pub width: u32,
+    pub value: u64,
+    pub fields: Vec<DecodedField>,
+}
+
+/// Decoded field value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedField {
+    pub name: String,
+    pub offset: u32,
+    pub width: u32,
+    pub value: u64,
+    /// Name of the field value, where defined
+    pub label: Option<String>,
+}
+
+/// Display the register value followed by a line per field
+impl std::fmt::Display for Decoded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = self.width as usize / 4;
+        write!(
+            f,
+            "{} (0x{:02x}) = 0x{:0w$x}",
+            self.name,
+            self.address,
+            self.value,
+            w = digits
+        )?;
+
+        let name_len = self.fields.iter().map(|d| d.name.len()).max().unwrap_or(0);
+
+        for d in &self.fields {
+            let bits = match d.width {
+                1 => format!("[{}]", d.offset),
+                w => format!("[{}:{}]", d.offset + w - 1, d.offset),
+            };
+
+            write!(
+                f,
+                "\n  {:n$} {:7} = 0x{:x}",
+                d.name,
+                bits,
+                d.value,
+                n = name_len
+            )?;
+
+            if let Some(l) = &d.label {
+                write!(f, " ({})", l)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Register access error
+#[derive(Debug)]
+pub enum RegisterError<E> {
+    /// Unknown register or field name
+    Unknown(String),
+    /// Register or field does not permit the access
+    Access { name: String, access: Access },
+    /// Value does not fit in the register or field
+    Range { name: String, value: u64 },
+    /// Underlying HAL error
+    Hal(E),
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for RegisterError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegisterError::Unknown(name) => write!(f, "Unknown register or field '{}'", name),
+            RegisterError::Access { name, access } => {
+                write!(f, "Register or field '{}' is {:?}", name, access)
+            }
+            RegisterError::Range { name, value } => {
+                write!(f, "Value 0x{:x} does not fit in '{}'", value, name)
+            }
+            RegisterError::Hal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for RegisterError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RegisterError::Hal(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl RegisterMap {
+    /// Load a register map file, selecting the format by file extension
+    pub fn load(file: &str) -> Result<Self, HalError> {
+        let m: RegisterMap = load_config(file)?;
+        m.validate()?;
+        Ok(m)
+    }
+
+    /// Parse register map data in the provided format, `path` is used for error reporting
+    pub fn parse(path: &str, data: &str, format: ConfigFormat) -> Result<Self, HalError> {
+        let m: RegisterMap = parse_config(path, data, format)?;
+        m.validate()?;
+        Ok(m)
+    }
+
+    /// Check the address width and all registers are valid
+    pub fn validate(&self) -> Result<(), HalError> {
+        if self.address_width == 0 || self.address_width > 8 {
+            return Err(HalError::InvalidConfig(format!(
+                "Register address width {} must be between 1 and 8 bytes",
+                self.address_width
+            )));
+        }
+
+        for r in &self.registers {
+            r.validate()
+                .map_err(|e| HalError::InvalidConfig(format!("Register {}: {}", r.name, e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Fetch a register by name (ignoring case)
+    pub fn get(&self, name: &str) -> Option<&Register> {
+        self.registers
+            .iter()
+            .find(|r| r.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Fetch a register and optional field by `REGISTER` or `REGISTER.FIELD` name
+    pub fn lookup(&self, name: &str) -> Option<(&Register, Option<&Field>)> {
+        match name.split_once('.') {
+            Some((r, f)) => {
+                let r = self.get(r)?;
+                Some((r, Some(r.field(f)?)))
+            }
+            None => Some((self.get(name)?, None)),
+        }
+    }
+
+    /// Fetch a register by address
+    pub fn at(&self, address: u64) -> Option<&Register> {
+        self.registers.iter().find(|r| r.address == address)
+    }
+
+    /// Encode the address prefix for a register access
+    pub fn prefix(&self, register: &Register, read: bool) -> Vec<u8> {
+        let a = register.address
+            | match read {
+                true => self.read_mask,
+                false => self.write_mask,
+            };
+
+        a.to_be_bytes()[8 - self.address_width..].to_vec()
+    }
+
+    /// Encode a register value
+    pub fn encode_value(&self, register: &Register, value: u64) -> Vec<u8> {
+        let n = register.len();
+
+        match self.endian {
+            Endian::Big => value.to_be_bytes()[8 - n..].to_vec(),
+            Endian::Little => value.to_le_bytes()[..n].to_vec(),
+        }
+    }
+
+    /// Decode a register value
+    pub fn decode_value(&self, data: &[u8]) -> u64 {
+        let fold = |v: u64, b: &u8| (v << 8) | *b as u64;
+
+        match self.endian {
+            Endian::Big => data.iter().fold(0, fold),
+            Endian::Little => data.iter().rev().fold(0, fold),
+        }
+    }
+
+    /// Decode a traced prefixed transaction, returning whether the access was a read
+    /// and the decoded register value where the prefix matches a register
+    pub fn decode_transaction(&self, prefix: &[u8], data: &[u8]) -> Option<(bool, Decoded)> {
+        if prefix.len() != self.address_width {
+            return None;
+        }
+
+        let a = prefix.iter().fold(0u64, |v, b| (v << 8) | *b as u64);
+
+        // Reads are identified by the read mask where distinct from the write mask
+        let read_bits = self.read_mask & !self.write_mask;
+        let read = read_bits != 0 && a & read_bits == read_bits;
+
+        let address = match read {
+            true => a & !self.read_mask,
+            false => a & !self.write_mask,
+        };
+
+        let r = self.at(address)?;
+        if data.len() != r.len() {
+            return None;
+        }
+
+        Some((read, r.decode(self.decode_value(data))))
+    }
+}
+
+/// Register access by name on top of `PrefixRead` / `PrefixWrite`
+pub struct Registers<H> {
+    map: RegisterMap,
+    pub hal: H,
+}
+
+impl<H> Registers<H> {
+    /// Create a register accessor using the provided map and HAL, validating the map
+    pub fn new(map: RegisterMap, hal: H) -> Result<Self, HalError> {
+        map.validate()?;
+        Ok(Self { map, hal })
+    }
+
+    /// Fetch the register map, which is fixed once validated by `new`
+    pub fn map(&self) -> &RegisterMap {
+        &self.map
+    }
+
+    /// Read a raw register value
+    fn read_register<E>(&mut self, r: &Register) -> Result<u64, RegisterError<E>>
+    where
+        H: PrefixRead<Error = E>,
+    {
+        if !r.access.readable() {
+            return Err(RegisterError::Access {
+                name: r.name.clone(),
+                access: r.access,
+            });
+        }
+
+        let mut d = vec![0u8; r.len()];
+        self.hal
+            .prefix_read(&self.map.prefix(r, true), &mut d)
+            .map_err(RegisterError::Hal)?;
+
+        Ok(self.map.decode_value(&d))
+    }
+
+    /// Read a register or field value by `REGISTER` or `REGISTER.FIELD` name
+    pub fn read<E>(&mut self, name: &str) -> Result<u64, RegisterError<E>>
+    where
+        H: PrefixRead<Error = E>,
+    {
+        let (r, f) = match self.map.lookup(name) {
+            Some((r, f)) => (r.clone(), f.cloned()),
+            None => return Err(RegisterError::Unknown(name.to_string())),
+        };
+
+        if let Some(f) = &f {
+            if !f.access.unwrap_or(r.access).readable() {
+                return Err(RegisterError::Access {
+                    name: name.to_string(),
+                    access: f.access.unwrap_or(r.access),
+                });
+            }
+        }
+
+        let v = self.read_register(&r)?;
+
+        Ok(match f {
+            Some(f) => f.extract(v),
+            None => v,
+        })
+    }
+
+    /// Read and decode a register by name
+    pub fn read_decoded<E>(&mut self, name: &str) -> Result<Decoded, RegisterError<E>>
+    where
+        H: PrefixRead<Error = E>,
+    {
+        let r = match self.map.lookup(name) {
+            Some((r, None)) => r.clone(),
+            _ => return Err(RegisterError::Unknown(name.to_string())),
+        };
+
+        let v = self.read_register(&r)?;
+
+        Ok(r.decode(v))
+    }
+
+    /// Read and decode all readable registers
+    pub fn dump<E>(&mut self) -> Result<Vec<Decoded>, RegisterError<E>>
+    where
+        H: PrefixRead<Error = E>,
+    {
+        let registers: Vec<_> = self
+            .map
+            .registers
+            .iter()
+            .filter(|r| r.access.readable())
+            .cloned()
+            .collect();
+
+        registers
+            .iter()
+            .map(|r| self.read_register(r).map(|v| r.decode(v)))
+            .collect()
+    }
+
+    /// Write a register or field value by `REGISTER` or `REGISTER.FIELD` name,
+    /// fields are updated by read-modify-write, or over the reset value for
+    /// write-only registers
+    pub fn write<E>(&mut self, name: &str, value: u64) -> Result<(), RegisterError<E>>
+    where
+        H: PrefixRead<Error = E> + PrefixWrite<Error = E>,
+    {
+        let (r, f) = match self.map.lookup(name) {
+            Some((r, f)) => (r.clone(), f.cloned()),
+            None => return Err(RegisterError::Unknown(name.to_string())),
+        };
+
+        let (access, width) = match &f {
+            Some(f) => (f.access.unwrap_or(r.access), f.width),
+            None => (r.access, r.width),
+        };
+
+        if !access.writable() || !r.access.writable() {
+            return Err(RegisterError::Access {
+                name: name.to_string(),
+                access,
+            });
+        }
+
+        if value & !mask(width) != 0 {
+            return Err(RegisterError::Range {
+                name: name.to_string(),
+                value,
+            });
+        }
+
+        let v = match &f {
+            Some(f) if r.access.readable() => f.insert(self.read_register(&r)?, value),
+            Some(f) => f.insert(r.reset, value),
+            None => value,
+        };
+
+        self.hal
+            .prefix_write(&self.map.prefix(&r, false), &self.map.encode_value(&r, v))
+            .map_err(RegisterError::Hal)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAP: &str = r#"
+        read_mask = 0x80
+
+        [[register]]
+        name = "STATUS"
+        address = 0x01
+        access = "ro"
+        fields = [
+            { name = "READY", offset = 0 },
+            { name = "MODE", offset = 1, width = 2, values = { sleep = 0, standby = 1, rx = 2 } },
+        ]
+
+        [[register]]
+        name = "FREQ"
+        address = "0x10"
+        width = 16
+
+        [[register]]
+        name = "CMD"
+        address = 0x20
+        access = "wo"
+        reset = 0x40
+        fields = [{ name = "GO", offset = 0 }]
+    "#;
+
+    fn map() -> RegisterMap {
+        RegisterMap::parse("a.toml", MAP, ConfigFormat::Toml).unwrap()
+    }
+
+    #[test]
+    fn test_parse_map() {
+        let m = map();
+
+        assert_eq!(m.registers.len(), 3);
+        assert_eq!(m.get("freq").unwrap().address, 0x10);
+        assert_eq!(m.get("CMD").unwrap().access, Access::WriteOnly);
+
+        let f = m.get("status").unwrap().field("mode").unwrap();
+        assert_eq!(f.extract(0b101), 0b10);
+        assert_eq!(f.insert(0b001, 1), 0b011);
+        assert_eq!(f.value_name(2), Some("rx"));
+        assert_eq!(f.parse_value("standby"), Ok(1));
+        assert_eq!(f.parse_value("0x2"), Ok(2));
+
+        // SVD-style names
+        let yaml = "registers:\n  - name: CTRL\n    addressOffset: 4\n    size: 32\n    fields:\n      - { name: EN, bitOffset: 31 }\n";
+        let m = RegisterMap::parse("a.yaml", yaml, ConfigFormat::Yaml).unwrap();
+        assert_eq!(m.get("CTRL").unwrap().len(), 4);
+
+        // Invalid maps are rejected when parsed
+        let bad = "[[register]]\nname = \"X\"\naddress = 1\nfields = [{ name = \"Y\", offset = 7, width = 2 }]";
+        assert!(RegisterMap::parse("a.toml", bad, ConfigFormat::Toml).is_err());
+
+        let bad = "address_width = 0\n[[register]]\nname = \"X\"\naddress = 1";
+        assert!(RegisterMap::parse("a.toml", bad, ConfigFormat::Toml).is_err());
+
+        let bad = "[[register]]\nname = \"X\"\naddress = 1\nfields = [{ name = \"Y\", offset = 4294967295, width = 2 }]";
+        assert!(RegisterMap::parse("a.toml", bad, ConfigFormat::Toml).is_err());
+
+        // Maps constructed directly are validated by `Registers`
+        let mut m = map();
+        m.registers[0].width = 72;
+        assert!(Registers::new(m, ()).is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        let m = map();
+
+        let d = m.get("STATUS").unwrap().decode(0x05);
+        assert_eq!(d.fields[1].label.as_deref(), Some("rx"));
+        assert_eq!(
+            d.to_string(),
+            "STATUS (0x01) = 0x05\n  READY [0]     = 0x1\n  MODE  [2:1]   = 0x2 (rx)"
+        );
+
+        let (read, d) = m.decode_transaction(&[0x90], &[0x12, 0x34]).unwrap();
+        assert!(read);
+        assert_eq!((d.name.as_str(), d.value), ("FREQ", 0x1234));
+
+        let (read, d) = m.decode_transaction(&[0x10], &[0x00, 0x01]).unwrap();
+        assert!(!read);
+        assert_eq!(d.value, 1);
+
+        assert!(m.decode_transaction(&[0x11], &[0x00]).is_none());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_registers() {
+        use crate::mock::{Mock, MockExec, MockTransaction};
+
+        let mut m = Mock::new();
+        let spi = m.spi();
+        let mut r = Registers::new(map(), spi.clone()).unwrap();
+
+        m.expect([
+            MockTransaction::spi_exec(
+                &spi,
+                [
+                    MockExec::SpiWrite(vec![0x81]),
+                    MockExec::SpiTransfer(vec![0x00], vec![0x03]),
+                ],
+            ),
+            MockTransaction::spi_exec(
+                &spi,
+                [
+                    MockExec::SpiWrite(vec![0x90]),
+                    MockExec::SpiTransfer(vec![0x00; 2], vec![0x00, 0x0F]),
+                ],
+            ),
+            MockTransaction::spi_exec(
+                &spi,
+                [
+                    MockExec::SpiWrite(vec![0x10]),
+                    MockExec::SpiWrite(vec![0x00, 0x0F]),
+                ],
+            ),
+            MockTransaction::spi_exec(
+                &spi,
+                [
+                    MockExec::SpiWrite(vec![0x20]),
+                    MockExec::SpiWrite(vec![0x41]),
+                ],
+            ),
+        ]);
+
+        assert_eq!(r.read("STATUS.MODE").unwrap(), 1);
+        assert_eq!(r.read_decoded("FREQ").unwrap().value, 0x0F);
+        r.write("FREQ", 0x0F).unwrap();
+
+        // Write-only fields are written over the reset value without a read
+        r.write("CMD.GO", 1).unwrap();
+        m.finalise();
+
+        // Access and range are checked before any transactions
+        assert!(matches!(r.read("CMD"), Err(RegisterError::Access { .. })));
+        assert!(matches!(
+            r.write("STATUS", 1),
+            Err(RegisterError::Access { .. })
+        ));
+        assert!(matches!(
+            r.write("FREQ", 0x10000),
+            Err(RegisterError::Range { .. })
+        ));
+        assert!(matches!(r.read("MISSING"), Err(RegisterError::Unknown(_))));
+    }
+}
@@ -0,0 +1,617 @@
+//! Declarative transaction scripts
+//!
+//! Scripts list SPI transactions, reset pin sets, expected reads (with optional bit masks),
+//! pin checks, waits and delays, and may be TOML, JSON or YAML as with device configurations.
+//! Data is hex encoded (`"0x9f00"`, `"de:ad:be:ef"`) or an array of bytes.
+//!
+//! ```toml
+//! [[step]]
+//! reset = "low"
+//!
+//! [[step]]
+//! delay_ms = 10
+//!
+//! [[step]]
+//! reset = "high"
+//!
+//! [[step]]
+//! wait = { pin = "ready", level = "high", timeout_ms = 100 }
+//!
+//! [[step]]
+//! prefix_read = { prefix = "80", expect = "5a00", mask = "ff00" }
+//!
+//! [[step]]
+//! write = { prefix = "81", data = "01" }
+//! ```
+//!
+//! Scripts run against any `Hal` implementation, and with the `mock` feature may be
+//! converted to a `mock::Mock` expectation list so bring-up sequences double as tests.
+
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use serde::Deserialize;
+
+use super::config::{load_config, parse_config, ConfigFormat};
+use super::HalError;
+use crate::{Hal, PinState};
+
+/// Transaction script
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Script {
+    /// Script steps, executed in order
+    #[serde(rename = "step", default)]
+    pub steps: Vec<Step>,
+}
+
+/// Script step
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// Write data, with an optional prefix
+    Write {
+        data: HexData,
+        prefix: Option<HexData>,
+    },
+    /// Transfer data, checking the data read where an expectation is provided
+    Transfer {
+        data: HexData,
+        expect: Option<HexData>,
+        mask: Option<HexData>,
+    },
+    /// Write a prefix then read `len` bytes (or the expectation length),
+    /// checking the data read where an expectation is provided
+    PrefixRead {
+        prefix: HexData,
+        len: Option<usize>,
+        expect: Option<HexData>,
+        mask: Option<HexData>,
+    },
+    /// Set the reset pin level
+    Reset(Level),
+    /// Check an input pin level
+    Check { pin: Input, level: Level },
+    /// Poll an input pin until the level is reached, failing on timeout
+    Wait {
+        pin: Input,
+        level: Level,
+        timeout_ms: u32,
+        #[serde(default = "default_poll_ms")]
+        poll_ms: u32,
+    },
+    /// Delay for a number of milliseconds
+    DelayMs(u32),
+    /// Delay for a number of microseconds
+    DelayUs(u32),
+}
+
+fn default_poll_ms() -> u32 {
+    1
+}
+
+/// Input pins available to scripts
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Input {
+    Busy,
+    Ready,
+}
+
+/// Logical pin level
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    High,
+    Low,
+}
+
+impl From<Level> for PinState {
+    fn from(l: Level) -> Self {
+        match l {
+            Level::High => PinState::High,
+            Level::Low => PinState::Low,
+        }
+    }
+}
+
+/// Byte data, serialised as a hex string or an array of bytes
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "HexDataRepr")]
+pub struct HexData(pub Vec<u8>);
+
+impl std::ops::Deref for HexData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HexDataRepr {
+    Hex(String),
+    Bytes(Vec<u8>),
+}
+
+impl std::convert::TryFrom<HexDataRepr> for HexData {
+    type Error = String;
+
+    fn try_from(r: HexDataRepr) -> Result<Self, Self::Error> {
+        match r {
+            HexDataRepr::Hex(s) => parse_hex(&s).map(HexData),
+            HexDataRepr::Bytes(b) => Ok(HexData(b)),
+        }
+    }
+}
+
+/// Parse hex data with an optional `0x` prefix, ignoring whitespace, `:` and `,` separators
+pub fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.trim();
+    let s = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+
+    let digits: Vec<char> = s
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':' && *c != ',')
+        .collect();
+
+    if digits.len() % 2 != 0 {
+        return Err(format!("hex data '{}' has an odd number of digits", s));
+    }
+
+    digits
+        .chunks(2)
+        .map(|c| {
+            let b: String = c.iter().collect();
+            u8::from_str_radix(&b, 16).map_err(|_| format!("invalid hex byte '{}'", b))
+        })
+        .collect()
+}
+
+/// Format data as a hex string
+pub fn format_hex(d: &[u8]) -> String {
+    d.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Script execution error
+#[derive(Debug)]
+pub enum ScriptError<E> {
+    /// Invalid step definition
+    Invalid { step: usize, message: String },
+    /// HAL error executing a step
+    Hal { step: usize, error: E },
+    /// Data read did not match the (masked) expectation
+    Mismatch {
+        step: usize,
+        expected: Vec<u8>,
+        mask: Option<Vec<u8>>,
+        actual: Vec<u8>,
+    },
+    /// Pin level did not match the expectation
+    PinMismatch {
+        step: usize,
+        pin: Input,
+        expected: Level,
+    },
+    /// Timeout waiting for a pin level
+    Timeout { step: usize, pin: Input },
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for ScriptError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptError::Invalid { step, message } => {
+                write!(f, "Invalid script step {}: {}", step, message)
+            }
+            ScriptError::Hal { step, error } => write!(f, "Script step {}: {}", step, error),
+            ScriptError::Mismatch {
+                step,
+                expected,
+                mask,
+                actual,
+            } => {
+                write!(
+                    f,
+                    "Script step {}: read {}, expected {}",
+                    step,
+                    format_hex(actual),
+                    format_hex(expected)
+                )?;
+                match mask {
+                    Some(m) => write!(f, " (mask {})", format_hex(m)),
+                    None => Ok(()),
+                }
+            }
+            ScriptError::PinMismatch {
+                step,
+                pin,
+                expected,
+            } => write!(
+                f,
+                "Script step {}: expected {:?} pin {:?}",
+                step, pin, expected
+            ),
+            ScriptError::Timeout { step, pin } => {
+                write!(f, "Script step {}: timeout waiting for {:?} pin", step, pin)
+            }
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ScriptError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScriptError::Hal { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Step {
+    /// Fetch the number of bytes read by this step
+    pub fn read_len(&self) -> Option<usize> {
+        match self {
+            Step::Transfer { data, .. } => Some(data.len()),
+            Step::PrefixRead { len, expect, .. } => {
+                len.or_else(|| expect.as_ref().map(|e| e.len()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Check expectation and mask lengths match the data read
+    pub fn validate(&self) -> Result<(), String> {
+        let (expect, mask) = match self {
+            Step::Transfer { expect, mask, .. } => (expect, mask),
+            Step::PrefixRead { expect, mask, .. } => (expect, mask),
+            Step::Wait { poll_ms: 0, .. } => return Err("poll_ms must be non-zero".to_string()),
+            _ => return Ok(()),
+        };
+
+        let len = match self.read_len() {
+            Some(l) => l,
+            None => return Err("prefix_read requires a len or expect value".to_string()),
+        };
+
+        match (expect, mask) {
+            (None, Some(_)) => Err("mask requires an expect value".to_string()),
+            (Some(e), _) if e.len() != len => Err(format!(
+                "expect length {} does not match read length {}",
+                e.len(),
+                len
+            )),
+            (_, Some(m)) if m.len() != len => Err(format!(
+                "mask length {} does not match read length {}",
+                m.len(),
+                len
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Check data read against an expectation, comparing only masked bits where provided
+fn check_read<E>(
+    step: usize,
+    actual: &[u8],
+    expect: &Option<HexData>,
+    mask: &Option<HexData>,
+) -> Result<(), ScriptError<E>> {
+    let expected = match expect {
+        Some(e) => e,
+        None => return Ok(()),
+    };
+
+    let matches = actual
+        .iter()
+        .zip(expected.iter())
+        .enumerate()
+        .all(|(i, (a, e))| {
+            let m = mask.as_ref().map(|m| m[i]).unwrap_or(0xFF);
+            a & m == e & m
+        });
+
+    match matches {
+        true => Ok(()),
+        false => Err(ScriptError::Mismatch {
+            step,
+            expected: expected.0.clone(),
+            mask: mask.as_ref().map(|m| m.0.clone()),
+            actual: actual.to_vec(),
+        }),
+    }
+}
+
+impl Script {
+    /// Load a script file, selecting the format by file extension
+    pub fn load(file: &str) -> Result<Self, HalError> {
+        let s: Script = load_config(file)?;
+        s.validate()?;
+        Ok(s)
+    }
+
+    /// Parse script data in the provided format, `path` is used for error reporting
+    pub fn parse(path: &str, data: &str, format: ConfigFormat) -> Result<Self, HalError> {
+        let s: Script = parse_config(path, data, format)?;
+        s.validate()?;
+        Ok(s)
+    }
+
+    /// Check all script steps are valid
+    pub fn validate(&self) -> Result<(), HalError> {
+        for (i, s) in self.steps.iter().enumerate() {
+            s.validate()
+                .map_err(|e| HalError::InvalidConfig(format!("Script step {}: {}", i, e)))?;
+        }
+        Ok(())
+    }
+
+    /// Execute the script against a HAL instance, returning the data read by each step
+    pub fn run<H, E>(&self, hal: &mut H) -> Result<Vec<Vec<u8>>, ScriptError<E>>
+    where
+        H: Hal<E>,
+    {
+        let mut reads = Vec::new();
+
+        for (i, s) in self.steps.iter().enumerate() {
+            debug!("Script step {}: {:?}", i, s);
+
+            s.validate()
+                .map_err(|message| ScriptError::<E>::Invalid { step: i, message })?;
+
+            let hal_err = |error| ScriptError::Hal { step: i, error };
+
+            match s {
+                Step::Write { data, prefix } => {
+                    let r = match prefix {
+                        Some(p) => hal.prefix_write(p, data),
+                        None => hal.write(data),
+                    };
+                    r.map_err(hal_err)?;
+                }
+                Step::Transfer { data, expect, mask } => {
+                    let mut d = data.0.clone();
+                    hal.transfer_in_place(&mut d).map_err(hal_err)?;
+
+                    check_read::<E>(i, &d, expect, mask)?;
+                    reads.push(d);
+                }
+                Step::PrefixRead {
+                    prefix,
+                    expect,
+                    mask,
+                    ..
+                } => {
+                    let mut d = vec![0u8; s.read_len().unwrap_or(0)];
+                    hal.prefix_read(prefix, &mut d).map_err(hal_err)?;
+
+                    check_read::<E>(i, &d, expect, mask)?;
+                    reads.push(d);
+                }
+                Step::Reset(l) => hal.set_reset((*l).into()).map_err(hal_err)?,
+                Step::Check { pin, level } => {
+                    if read_pin(hal, *pin).map_err(hal_err)? != PinState::from(*level) {
+                        return Err(ScriptError::PinMismatch {
+                            step: i,
+                            pin: *pin,
+                            expected: *level,
+                        });
+                    }
+                }
+                Step::Wait {
+                    pin,
+                    level,
+                    timeout_ms,
+                    poll_ms,
+                } => {
+                    let mut elapsed = 0;
+
+                    while read_pin(hal, *pin).map_err(hal_err)? != PinState::from(*level) {
+                        if elapsed >= *timeout_ms {
+                            return Err(ScriptError::Timeout { step: i, pin: *pin });
+                        }
+
+                        hal.delay_us(poll_ms.saturating_mul(1000));
+                        elapsed = elapsed.saturating_add(*poll_ms);
+                    }
+                }
+                Step::DelayMs(ms) => hal.delay_us(ms.saturating_mul(1000)),
+                Step::DelayUs(us) => hal.delay_us(*us),
+            }
+        }
+
+        Ok(reads)
+    }
+
+    /// Convert the script to a mock expectation list, with reads returning the
+    /// expected data (or zeros where no expectation is provided) and pins reading
+    /// the expected levels
+    #[cfg(feature = "mock")]
+    pub fn expectations(
+        &self,
+        spi: &crate::mock::Spi,
+    ) -> Result<Vec<crate::mock::MockTransaction>, HalError> {
+        use crate::mock::{MockExec, MockTransaction as T};
+
+        self.validate()?;
+
+        let read = |s: &Step, expect: &Option<HexData>| match expect {
+            Some(e) => e.0.clone(),
+            None => vec![0u8; s.read_len().unwrap_or(0)],
+        };
+        let pin = |p: &Input, l: &Level| match p {
+            Input::Busy => T::busy(spi, (*l).into()),
+            Input::Ready => T::ready(spi, (*l).into()),
+        };
+
+        let t = self
+            .steps
+            .iter()
+            .map(|s| match s {
+                Step::Write { data, prefix: None } => T::write(spi, data.0.clone()),
+                Step::Write {
+                    data,
+                    prefix: Some(p),
+                } => T::spi_exec(
+                    spi,
+                    [
+                        MockExec::SpiWrite(p.0.clone()),
+                        MockExec::SpiWrite(data.0.clone()),
+                    ],
+                ),
+                Step::Transfer { data, expect, .. } => {
+                    T::transfer(spi, data.0.clone(), read(s, expect))
+                }
+                Step::PrefixRead { prefix, expect, .. } => T::spi_exec(
+                    spi,
+                    [
+                        MockExec::SpiWrite(prefix.0.clone()),
+                        MockExec::SpiTransfer(
+                            vec![0u8; s.read_len().unwrap_or(0)],
+                            read(s, expect),
+                        ),
+                    ],
+                ),
+                Step::Reset(l) => T::reset(spi, (*l).into()),
+                Step::Check { pin: p, level } => pin(p, level),
+                Step::Wait { pin: p, level, .. } => pin(p, level),
+                Step::DelayMs(ms) => T::delay_ms(*ms),
+                Step::DelayUs(us) => T::DelayUs(*us),
+            })
+            .collect();
+
+        Ok(t)
+    }
+}
+
+/// Read an input pin by name
+fn read_pin<H: Hal<E>, E>(hal: &mut H, pin: Input) -> Result<PinState, E> {
+    match pin {
+        Input::Busy => hal.get_busy(),
+        Input::Ready => hal.get_ready(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SCRIPT: &str = r#"
+        [[step]]
+        reset = "low"
+
+        [[step]]
+        delay_ms = 10
+
+        [[step]]
+        reset = "high"
+
+        [[step]]
+        wait = { pin = "ready", level = "high", timeout_ms = 100 }
+
+        [[step]]
+        prefix_read = { prefix = "80", expect = "5a00", mask = "ff00" }
+
+        [[step]]
+        transfer = { data = [1, 2], expect = "0x0304" }
+
+        [[step]]
+        write = { prefix = "81", data = "01" }
+
+        [[step]]
+        check = { pin = "busy", level = "low" }
+    "#;
+
+    #[test]
+    fn test_parse_script() {
+        let s = Script::parse("a.toml", SCRIPT, ConfigFormat::Toml).unwrap();
+        assert!(s.validate().is_ok());
+
+        assert_eq!(s.steps.len(), 8);
+        assert_eq!(s.steps[0], Step::Reset(Level::Low));
+        assert_eq!(s.steps[1], Step::DelayMs(10));
+        assert_eq!(
+            s.steps[3],
+            Step::Wait {
+                pin: Input::Ready,
+                level: Level::High,
+                timeout_ms: 100,
+                poll_ms: 1
+            }
+        );
+        assert_eq!(s.steps[4].read_len(), Some(2));
+        assert_eq!(
+            s.steps[5],
+            Step::Transfer {
+                data: HexData(vec![1, 2]),
+                expect: Some(HexData(vec![3, 4])),
+                mask: None,
+            }
+        );
+
+        let yaml = "step:\n  - prefix_read: { prefix: \"80\", len: 2, mask: \"ff00\" }\n";
+        assert!(Script::parse("a.yaml", yaml, ConfigFormat::Yaml).is_err());
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("0x9f00").unwrap(), vec![0x9f, 0x00]);
+        assert_eq!(
+            parse_hex("de:ad be,EF").unwrap(),
+            vec![0xde, 0xad, 0xbe, 0xef]
+        );
+        assert_eq!(parse_hex("").unwrap(), Vec::<u8>::new());
+
+        assert!(parse_hex("abc").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_script_mock() {
+        use crate::mock::{Mock, MockTransaction};
+
+        let s = Script::parse("a.toml", SCRIPT, ConfigFormat::Toml).unwrap();
+
+        let mut m = Mock::new();
+        let mut spi = m.spi();
+
+        m.expect(s.expectations(&spi).unwrap());
+        let reads = s.run(&mut spi).unwrap();
+        m.finalise();
+
+        assert_eq!(reads, vec![vec![0x5a, 0x00], vec![0x03, 0x04]]);
+
+        // Masked bits are ignored while others must match
+        let mut e = s.expectations(&spi).unwrap();
+        e[4] = MockTransaction::spi_exec(
+            &spi,
+            [
+                crate::mock::MockExec::SpiWrite(vec![0x80]),
+                crate::mock::MockExec::SpiTransfer(vec![0x00; 2], vec![0x5a, 0xFF]),
+            ],
+        );
+        m.expect(e.clone());
+        assert!(s.run(&mut spi).is_ok());
+        m.finalise();
+
+        e[4] = MockTransaction::spi_exec(
+            &spi,
+            [
+                crate::mock::MockExec::SpiWrite(vec![0x80]),
+                crate::mock::MockExec::SpiTransfer(vec![0x00; 2], vec![0x5b, 0x00]),
+            ],
+        );
+        m.expect(e.clone());
+        match s.run(&mut spi) {
+            Err(ScriptError::Mismatch { step: 4, .. }) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        // Execution stops at the mismatch leaving later steps unconsumed
+        assert_eq!(m.remaining(), e[5..].to_vec());
+    }
+}
@@ -0,0 +1,391 @@
+//! Status indicator LEDs
+//!
+//! `StatusLeds` drives the `led0` / `led1` pins as activity and error indicators,
+//! with a background thread handling activity blink timeouts and the idle heartbeat.
+//! `StatusHal` wraps a HAL instance to signal activity on each SPI transaction and
+//! latch the error LED on failure.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
+
+use super::{HalInst, HalOutputPin};
+use crate::{Busy, ManagedChipSelect, PinState, Ready, Reset};
+
+/// Status indicator configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusConfig {
+    /// Duration the activity LED is lit for each transaction
+    pub blink: Duration,
+    /// Heartbeat period while idle, `None` to disable
+    pub heartbeat: Option<Duration>,
+    /// Background thread update interval
+    pub tick: Duration,
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            blink: Duration::from_millis(20),
+            heartbeat: Some(Duration::from_secs(1)),
+            tick: Duration::from_millis(10),
+        }
+    }
+}
+
+struct State {
+    activity: HalOutputPin,
+    error: HalOutputPin,
+    active_until: Option<Instant>,
+    last_beat: Instant,
+    errored: bool,
+}
+
+impl State {
+    /// Light the activity LED if idle and extend the blink deadline
+    fn activity(&mut self, config: &StatusConfig, now: Instant) {
+        if self.active_until.is_none() {
+            set(&mut self.activity, true);
+        }
+        self.active_until = Some(now + config.blink);
+    }
+
+    fn update(&mut self, config: &StatusConfig, now: Instant) {
+        // Clear expired activity blink
+        if let Some(t) = self.active_until {
+            if now >= t {
+                set(&mut self.activity, false);
+                self.active_until = None;
+                self.last_beat = now;
+            }
+            return;
+        }
+
+        // Pulse heartbeat while idle
+        if let Some(period) = config.heartbeat {
+            if now.duration_since(self.last_beat) >= period {
+                self.activity(config, now);
+            }
+        }
+    }
+}
+
+/// Set an LED, ignoring unbound pins and logging failures so indicators never
+/// interrupt bus operations
+fn set(pin: &mut HalOutputPin, on: bool) {
+    if let HalOutputPin::None = pin {
+        return;
+    }
+
+    let r = match on {
+        true => pin.set_high(),
+        false => pin.set_low(),
+    };
+
+    if let Err(e) = r {
+        warn!("Failed to set status LED: {}", e);
+    }
+}
+
+/// Status indicator LEDs with activity blink, error latch and heartbeat
+pub struct StatusLeds {
+    state: Arc<Mutex<State>>,
+    config: StatusConfig,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatusLeds {
+    /// Create status indicators using the provided activity and error LEDs,
+    /// starting the background update thread
+    pub fn new(activity: HalOutputPin, error: HalOutputPin, config: StatusConfig) -> Self {
+        let mut state = State {
+            activity,
+            error,
+            active_until: None,
+            last_beat: Instant::now(),
+            errored: false,
+        };
+        set(&mut state.activity, false);
+        set(&mut state.error, false);
+
+        let state = Arc::new(Mutex::new(state));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread = {
+            let (state, running, config) = (state.clone(), running.clone(), config.clone());
+
+            std::thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    state.lock().unwrap().update(&config, Instant::now());
+                    std::thread::sleep(config.tick);
+                }
+            })
+        };
+
+        Self {
+            state,
+            config,
+            running,
+            thread: Some(thread),
+        }
+    }
+
+    /// Signal bus activity, lighting the activity LED for the configured blink time
+    pub fn activity(&self) {
+        self.state
+            .lock()
+            .unwrap()
+            .activity(&self.config, Instant::now());
+    }
+
+    /// Latch the error LED on until cleared
+    pub fn error(&self) {
+        let mut s = self.state.lock().unwrap();
+
+        set(&mut s.error, true);
+        s.errored = true;
+    }
+
+    /// Clear a latched error
+    pub fn clear_error(&self) {
+        let mut s = self.state.lock().unwrap();
+
+        set(&mut s.error, false);
+        s.errored = false;
+    }
+
+    /// Check whether an error is latched
+    pub fn is_errored(&self) -> bool {
+        self.state.lock().unwrap().errored
+    }
+}
+
+/// Stop the background thread and turn the LEDs off
+impl Drop for StatusLeds {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+
+        if let Some(t) = self.thread.take() {
+            let _ = t.join();
+        }
+
+        let mut s = self.state.lock().unwrap();
+        set(&mut s.activity, false);
+        set(&mut s.error, false);
+    }
+}
+
+/// HAL wrapper signalling activity and errors on status LEDs
+pub struct StatusHal<H> {
+    pub inner: H,
+    pub leds: StatusLeds,
+}
+
+impl<H> StatusHal<H> {
+    /// Wrap a HAL instance with status indicators
+    pub fn new(inner: H, leds: StatusLeds) -> Self {
+        Self { inner, leds }
+    }
+
+    /// Latch the error LED on failure
+    fn check<T, E>(&self, r: Result<T, E>) -> Result<T, E> {
+        if r.is_err() {
+            self.leds.error();
+        }
+        r
+    }
+}
+
+impl HalInst {
+    /// Wrap this instance with status indicators, using `led0` for activity
+    /// and heartbeat and `led1` for errors
+    pub fn with_status(mut self, config: StatusConfig) -> StatusHal<HalInst> {
+        let activity = std::mem::replace(&mut self.pins.led0, HalOutputPin::None);
+        let error = std::mem::replace(&mut self.pins.led1, HalOutputPin::None);
+
+        StatusHal::new(self, StatusLeds::new(activity, error, config))
+    }
+}
+
+impl<H: ManagedChipSelect> ManagedChipSelect for StatusHal<H> {}
+
+impl<H: ErrorType> ErrorType for StatusHal<H> {
+    type Error = H::Error;
+}
+
+impl<H: SpiDevice<u8>> SpiDevice<u8> for StatusHal<H> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        self.leds.activity();
+
+        let r = self.inner.transaction(operations);
+        self.check(r)
+    }
+}
+
+impl<H: Reset> Reset for StatusHal<H> {
+    type Error = H::Error;
+
+    fn set_reset(&mut self, state: PinState) -> Result<(), Self::Error> {
+        let r = self.inner.set_reset(state);
+        self.check(r)
+    }
+}
+
+impl<H: Busy> Busy for StatusHal<H> {
+    type Error = H::Error;
+
+    fn get_busy(&mut self) -> Result<PinState, Self::Error> {
+        let r = self.inner.get_busy();
+        self.check(r)
+    }
+}
+
+impl<H: Ready> Ready for StatusHal<H> {
+    type Error = H::Error;
+
+    fn get_ready(&mut self) -> Result<PinState, Self::Error> {
+        let r = self.inner.get_ready();
+        self.check(r)
+    }
+}
+
+impl<H: embedded_hal::delay::DelayUs> embedded_hal::delay::DelayUs for StatusHal<H> {
+    fn delay_us(&mut self, us: u32) {
+        self.inner.delay_us(us)
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.inner.delay_ms(ms)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::boxed::Box;
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::super::{HalBase, HalError, HalInputPin, HalPins, HalSpi};
+    use super::*;
+    use crate::Hal;
+
+    type Levels = Arc<Mutex<Vec<bool>>>;
+
+    /// Output pin recording the set levels
+    struct Recorder(Levels);
+
+    impl OutputPin for Recorder {
+        fn set_high(&mut self) -> Result<(), HalError> {
+            self.0.lock().unwrap().push(true);
+            Ok(())
+        }
+
+        fn set_low(&mut self) -> Result<(), HalError> {
+            self.0.lock().unwrap().push(false);
+            Ok(())
+        }
+    }
+
+    impl embedded_hal::digital::ErrorType for Recorder {
+        type Error = HalError;
+    }
+
+    /// SPI device failing every other transaction
+    struct Flaky(bool);
+
+    impl SpiDevice<u8> for Flaky {
+        fn transaction(&mut self, _ops: &mut [Operation<'_, u8>]) -> Result<(), HalError> {
+            self.0 = !self.0;
+            match self.0 {
+                true => Ok(()),
+                false => Err(HalError::Timeout),
+            }
+        }
+    }
+
+    impl ErrorType for Flaky {
+        type Error = HalError;
+    }
+
+    fn assert_hal<H: Hal<HalError>>(_h: &H) {}
+
+    #[test]
+    fn test_status_hal() {
+        let (activity, error) = (Levels::default(), Levels::default());
+
+        let h = HalInst {
+            base: HalBase::None,
+            spi: HalSpi::Dyn(Box::new(Flaky(false))),
+            pins: HalPins {
+                cs: HalOutputPin::Dyn(Box::new(Recorder(Levels::default()))),
+                reset: HalOutputPin::None,
+                busy: HalInputPin::None,
+                ready: HalInputPin::None,
+                led0: HalOutputPin::Dyn(Box::new(Recorder(activity.clone()))),
+                led1: HalOutputPin::Dyn(Box::new(Recorder(error.clone()))),
+            },
+        };
+
+        // Blink long enough that the background thread never clears it
+        let config = StatusConfig {
+            blink: Duration::from_secs(3600),
+            heartbeat: None,
+            ..Default::default()
+        };
+        let mut h = h.with_status(config.clone());
+        assert_hal(&h);
+
+        // Activity lights the LED once per blink and is cleared on expiry
+        h.write(&[0xAA]).unwrap();
+        h.leds.activity();
+        assert_eq!(*activity.lock().unwrap(), vec![false, true]);
+
+        let expiry = Instant::now() + config.blink;
+        h.leds.state.lock().unwrap().update(&config, expiry);
+        assert_eq!(*activity.lock().unwrap(), vec![false, true, false]);
+
+        // Errors are latched until cleared
+        assert!(!h.leds.is_errored());
+        h.write(&[0xBB]).unwrap_err();
+        assert!(h.leds.is_errored());
+        h.write(&[0xCC]).unwrap();
+        assert!(h.leds.is_errored());
+        assert_eq!(*error.lock().unwrap(), vec![false, true]);
+
+        h.leds.clear_error();
+        assert_eq!(*error.lock().unwrap(), vec![false, true, false]);
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let activity = Levels::default();
+
+        let config = StatusConfig {
+            blink: Duration::from_millis(5),
+            heartbeat: Some(Duration::from_millis(20)),
+            tick: Duration::from_millis(1),
+        };
+
+        let start = Instant::now();
+        let mut s = State {
+            activity: HalOutputPin::Dyn(Box::new(Recorder(activity.clone()))),
+            error: HalOutputPin::None,
+            active_until: None,
+            last_beat: start,
+            errored: false,
+        };
+
+        // Step through 100ms of ticks, pulsing every heartbeat period
+        for t in 0..100 {
+            s.update(&config, start + Duration::from_millis(t));
+        }
+
+        let beats = activity.lock().unwrap().iter().filter(|v| **v).count();
+        assert_eq!(beats, 4);
+    }
+}
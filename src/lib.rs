@@ -6,7 +6,7 @@
 //! `driver_pal::Transactional` interface, as well as a set of helpers for C compatibility enabled with
 //! the `compat` feature, and a basic mocking adaptor enabled with the `mock` feature.
 
-#![cfg_attr(not(feature = "hal"), no_std)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 #[macro_use]
 extern crate log;
@@ -19,30 +19,51 @@ extern crate std;
 #[cfg(feature = "mock")]
 pub mod mock;
 
-#[cfg(feature = "ffi")]
+#[cfg(any(feature = "ffi", feature = "hal-linux"))]
 extern crate libc;
 
 #[cfg(feature = "ffi")]
 pub mod ffi;
 
+#[cfg(feature = "python")]
+extern crate pyo3;
+
+#[cfg(feature = "python")]
+pub mod python;
+
 #[cfg(feature = "serde")]
 extern crate serde;
 
 #[cfg(feature = "toml")]
 extern crate toml;
 
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
+
+#[cfg(feature = "serde_yaml")]
+extern crate serde_yaml;
+
 #[cfg(feature = "simplelog")]
 extern crate simplelog;
 
 #[cfg(feature = "hal-linux")]
 extern crate linux_embedded_hal;
 
+#[cfg(feature = "hal-linux")]
+extern crate gpio_cdev;
+
 #[cfg(feature = "hal-cp2130")]
 extern crate driver_cp2130;
 
+#[cfg(feature = "hal-ftdi")]
+extern crate ftdi_embedded_hal;
+
 #[cfg(feature = "hal")]
 pub mod hal;
 
+#[cfg(feature = "utils")]
+pub mod utils;
+
 pub mod wrapper;
 
 /// ManagedChipSelect marker trait indicates CS is managed by the driver
@@ -125,34 +146,98 @@ pub trait Ready {
     fn get_ready(&mut self) -> Result<PinState, Self::Error>;
 }
 
+/// Edge selection for `WaitForEdge`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Rising,
+    Falling,
+    Both,
+}
+
+/// WaitForEdge trait for input pins supporting blocking edge (interrupt) waits
+pub trait WaitForEdge {
+    type Error;
+
+    /// Block until the selected edge occurs or the timeout elapses,
+    /// returning `true` if the edge occurred and `false` on timeout
+    fn wait_for_edge(
+        &mut self,
+        edge: Edge,
+        timeout: core::time::Duration,
+    ) -> Result<bool, Self::Error>;
+}
+
+/// PinRole identifies the function of a pin for error reporting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PinRole {
+    ChipSelect,
+    Reset,
+    Busy,
+    Ready,
+}
+
 /// Error type combining SPI and Pin errors for utility
 #[derive(Debug, Clone, PartialEq)]
 pub enum Error<SpiError, PinError> {
     Spi(SpiError),
-    Pin(PinError),
+    Pin(PinRole, PinError),
     Aborted,
+    Timeout,
 }
 
+/// SPI error kinds are propagated from the inner SPI error,
+/// with chip select pin failures reported as `ChipSelectFault`
 impl<SpiError, PinError> embedded_hal::spi::Error for Error<SpiError, PinError>
 where
-    SpiError: core::fmt::Debug,
+    SpiError: embedded_hal::spi::Error,
     PinError: core::fmt::Debug,
 {
     fn kind(&self) -> embedded_hal::spi::ErrorKind {
-        embedded_hal::spi::ErrorKind::Other
+        match self {
+            Error::Spi(e) => e.kind(),
+            Error::Pin(PinRole::ChipSelect, _) => embedded_hal::spi::ErrorKind::ChipSelectFault,
+            _ => embedded_hal::spi::ErrorKind::Other,
+        }
     }
 }
 
+/// Digital error kinds are propagated from the inner pin error
 impl<SpiError, PinError> embedded_hal::digital::Error for Error<SpiError, PinError>
 where
     SpiError: core::fmt::Debug,
-    PinError: core::fmt::Debug,
+    PinError: embedded_hal::digital::Error,
 {
     fn kind(&self) -> embedded_hal::digital::ErrorKind {
-        embedded_hal::digital::ErrorKind::Other
+        match self {
+            Error::Pin(_, e) => e.kind(),
+            _ => embedded_hal::digital::ErrorKind::Other,
+        }
     }
 }
 
+impl<SpiError, PinError> core::fmt::Display for Error<SpiError, PinError>
+where
+    SpiError: core::fmt::Debug,
+    PinError: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Spi(e) => write!(f, "SPI error: {:?}", e),
+            Error::Pin(role, e) => write!(f, "{:?} pin error: {:?}", role, e),
+            Error::Aborted => write!(f, "Operation aborted"),
+            Error::Timeout => write!(f, "Operation timed out"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<SpiError, PinError> std::error::Error for Error<SpiError, PinError>
+where
+    SpiError: core::fmt::Debug,
+    PinError: core::fmt::Debug,
+{
+}
+
 /// PinState enum used for busy indication
 #[derive(Debug, Clone, PartialEq)]
 pub enum PinState {
@@ -197,3 +282,33 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal::digital::ErrorKind as PinErrorKind;
+    use embedded_hal::spi::ErrorKind as SpiErrorKind;
+
+    type E = Error<SpiErrorKind, PinErrorKind>;
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(
+            embedded_hal::spi::Error::kind(&E::Spi(SpiErrorKind::Overrun)),
+            SpiErrorKind::Overrun
+        );
+        assert_eq!(
+            embedded_hal::spi::Error::kind(&E::Pin(PinRole::ChipSelect, PinErrorKind::Other)),
+            SpiErrorKind::ChipSelectFault
+        );
+        assert_eq!(
+            embedded_hal::spi::Error::kind(&E::Timeout),
+            SpiErrorKind::Other
+        );
+        assert_eq!(
+            embedded_hal::digital::Error::kind(&E::Pin(PinRole::Busy, PinErrorKind::Other)),
+            PinErrorKind::Other
+        );
+    }
+}
@@ -1,8 +1,9 @@
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::vec;
 use std::vec::Vec;
 
-use crate::{Busy, PinState, Ready, Reset};
+use crate::{Busy, Edge, PinState, Ready, Reset, WaitForEdge};
 
 use embedded_hal::spi::Operation as SpiOperation;
 
@@ -76,6 +77,7 @@ pub enum MockTransaction {
     IsLow(Id, bool),
     SetHigh(Id),
     SetLow(Id),
+    WaitForEdge(Id, Edge, Duration, bool),
 
     DelayUs(u32),
 }
@@ -117,7 +119,7 @@ impl MockTransaction {
     }
 
     pub fn delay_ms(v: u32) -> Self {
-        MockTransaction::DelayUs(v * 1000)
+        MockTransaction::DelayUs(v.saturating_mul(1000))
     }
 
     pub fn write<B>(spi: &Spi, outgoing: B) -> Self
@@ -153,23 +155,34 @@ impl MockTransaction {
     pub fn set_low(pin: &Pin) -> Self {
         MockTransaction::SetLow(pin.id)
     }
+
+    /// Expect an edge wait, `occurred` sets whether the edge is reported or the wait times out
+    pub fn wait_for_edge(pin: &Pin, edge: Edge, timeout: Duration, occurred: bool) -> Self {
+        MockTransaction::WaitForEdge(pin.id, edge, timeout, occurred)
+    }
 }
 
 /// MockExec type for composing mock exec transactions
 #[derive(Clone, Debug, PartialEq)]
 pub enum MockExec {
     SpiWrite(Vec<u8>),
+    SpiRead(Vec<u8>),
     SpiTransfer(Vec<u8>, Vec<u8>),
+    DelayUs(u32),
 }
 
 impl<'a> From<&SpiOperation<'a, u8>> for MockExec {
     fn from(t: &SpiOperation<'a, u8>) -> Self {
         match t {
             SpiOperation::Write(ref d) => MockExec::SpiWrite(d.to_vec()),
+            SpiOperation::Read(ref d) => MockExec::SpiRead(vec![0u8; d.len()]),
+            SpiOperation::Transfer(ref r, ref w) => {
+                MockExec::SpiTransfer(w.to_vec(), vec![0u8; r.len()])
+            }
             SpiOperation::TransferInPlace(ref d) => {
                 MockExec::SpiTransfer(d.to_vec(), vec![0u8; d.len()])
             }
-            _ => todo!(),
+            SpiOperation::DelayUs(us) => MockExec::DelayUs(*us),
         }
     }
 }
@@ -250,10 +263,17 @@ impl Mock {
         let mut i = self.inner.lock().unwrap();
         i.finalise();
     }
+
+    /// Fetch expectations not yet consumed, for checking partially executed sequences
+    pub fn remaining(&self) -> Vec<MockTransaction> {
+        let i = self.inner.lock().unwrap();
+        i.expected.iter().skip(i.index).cloned().collect()
+    }
 }
 
+// Pin functions on the SPI mock share the SPI error type so `Spi` implements `Hal`
 impl Busy for Spi {
-    type Error = PinError;
+    type Error = MockError;
 
     /// Check peripheral busy status
     fn get_busy(&mut self) -> Result<PinState, Self::Error> {
@@ -274,7 +294,7 @@ impl Busy for Spi {
 }
 
 impl Ready for Spi {
-    type Error = PinError;
+    type Error = MockError;
 
     /// Check peripheral ready status
     fn get_ready(&mut self) -> Result<PinState, Self::Error> {
@@ -296,7 +316,7 @@ impl Ready for Spi {
 }
 
 impl Reset for Spi {
-    type Error = PinError;
+    type Error = MockError;
 
     /// Check peripheral ready status
     fn set_reset(&mut self, state: PinState) -> Result<(), Self::Error> {
@@ -327,13 +347,12 @@ impl embedded_hal::spi::SpiDevice<u8> for Spi {
         let mut i = self.inner.lock().unwrap();
         let index = i.index;
 
-        // Save actual calls
-        let t: Vec<MockExec> = operations
+        // Save outgoing data prior to loading expected reads
+        let outgoing: Vec<MockExec> = operations
             .as_mut()
             .iter()
             .map(|ref v| MockExec::from(*v))
             .collect();
-        i.actual.push(MockTransaction::SpiExec(self.id, t));
 
         let transactions = operations.as_mut();
 
@@ -348,6 +367,15 @@ impl embedded_hal::spi::SpiDevice<u8> for Spi {
                         SpiOperation::TransferInPlace(ref mut t_in),
                         Some(MockExec::SpiTransfer(_x_out, x_in)),
                     ) => t_in.copy_from_slice(&x_in),
+                    (
+                        SpiOperation::Transfer(ref mut t_in, _),
+                        Some(MockExec::SpiTransfer(_, x_in)),
+                    )
+                    | (SpiOperation::Read(ref mut t_in), Some(MockExec::SpiRead(x_in)))
+                        if t_in.len() == x_in.len() =>
+                    {
+                        t_in.copy_from_slice(&x_in)
+                    }
                     (SpiOperation::Write(ref _t_out), Some(MockExec::SpiWrite(ref _x_out))) => {
                         //assert_eq!(t_out, x_out);
                     }
@@ -356,6 +384,23 @@ impl embedded_hal::spi::SpiDevice<u8> for Spi {
             }
         }
 
+        // Save actual calls, including the data read
+        let t = outgoing
+            .into_iter()
+            .zip(transactions.iter())
+            .map(|(o, t)| match (o, t) {
+                (MockExec::SpiTransfer(x_out, _), SpiOperation::TransferInPlace(t_in))
+                | (MockExec::SpiTransfer(x_out, _), SpiOperation::Transfer(t_in, _)) => {
+                    MockExec::SpiTransfer(x_out, t_in.to_vec())
+                }
+                (MockExec::SpiRead(_), SpiOperation::Read(t_in)) => {
+                    MockExec::SpiRead(t_in.to_vec())
+                }
+                (o, _) => o,
+            })
+            .collect();
+        i.actual.push(MockTransaction::SpiExec(self.id, t));
+
         // Update expectation index
         i.index += 1;
 
@@ -474,6 +519,30 @@ impl embedded_hal::digital::ErrorType for Pin {
     type Error = PinError;
 }
 
+impl WaitForEdge for Pin {
+    type Error = PinError;
+
+    fn wait_for_edge(&mut self, edge: Edge, timeout: Duration) -> Result<bool, Self::Error> {
+        let mut i = self.inner.lock().unwrap();
+        let index = i.index;
+
+        // Fetch expectation if found, otherwise time out
+        let v = match &i.expected.get(index) {
+            Some(MockTransaction::WaitForEdge(_id, _edge, _timeout, v)) => *v,
+            _ => false,
+        };
+
+        // Save actual call
+        i.actual
+            .push(MockTransaction::WaitForEdge(self.id, edge, timeout, v));
+
+        // Update expectation index
+        i.index += 1;
+
+        Ok(v)
+    }
+}
+
 impl embedded_hal::delay::DelayUs for Delay {
     fn delay_us(&mut self, t: u32) {
         let mut i = self.inner.lock().unwrap();
@@ -498,9 +567,7 @@ mod test {
     use super::*;
     use crate::{PrefixRead, PrefixWrite};
 
-    // TODO: needs fixing
     #[test]
-    #[ignore]
     fn test_transactional_read() {
         let mut m = Mock::new();
         let mut s = m.spi();
@@ -642,6 +709,23 @@ mod test {
         m.finalise();
     }
 
+    #[test]
+    fn test_wait_for_edge() {
+        let mut m = Mock::new();
+        let mut p = m.pin();
+        let timeout = Duration::from_millis(100);
+
+        m.expect(vec![
+            MockTransaction::wait_for_edge(&p, Edge::Rising, timeout, true),
+            MockTransaction::wait_for_edge(&p, Edge::Falling, timeout, false),
+        ]);
+
+        assert!(p.wait_for_edge(Edge::Rising, timeout).unwrap());
+        assert!(!p.wait_for_edge(Edge::Falling, timeout).unwrap());
+
+        m.finalise();
+    }
+
     #[test]
     #[should_panic]
     fn test_incorrect_pin() {
@@ -0,0 +1,485 @@
+//! Python bindings for the hal runtime
+//!
+//! Enabled with the `python` feature and built as an extension module with `maturin`
+//! (see `pyproject.toml`), this exposes `DeviceConfig` and a `Hal` device wrapping
+//! `hal::HalInst`, as well as `Mock` and `MockTransaction` so bring-up scripts can be
+//! tested against the mock backend with pytest.
+//!
+//! ```python
+//! import driver_pal
+//!
+//! config = driver_pal.DeviceConfig.load("radio.toml")
+//! hal = driver_pal.Hal(config)
+//!
+//! hal.reset()
+//! hal.wait_busy(100)
+//! print(hal.prefix_read([0x80], 2).hex())
+//! ```
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::string::{String, ToString};
+use std::time::{Duration, Instant};
+use std::vec;
+use std::vec::Vec;
+
+use embedded_hal::delay::DelayUs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::spi::SpiDevice;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyAssertionError, PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::hal::{self, ConfigFormat, DeviceConfig, HalInst};
+use crate::mock::{self, MockExec, MockTransaction};
+use crate::{Busy, PinState, PrefixRead, PrefixWrite, Ready, Reset};
+
+create_exception!(driver_pal, DriverPalError, PyException);
+
+/// Convert an error to a python `DriverPalError`
+fn py_err<E: std::fmt::Display>(e: E) -> PyErr {
+    DriverPalError::new_err(e.to_string())
+}
+
+fn pin_state(v: bool) -> PinState {
+    match v {
+        true => PinState::High,
+        false => PinState::Low,
+    }
+}
+
+/// Parse a configuration format name
+fn config_format(format: &str) -> Option<ConfigFormat> {
+    match format.to_lowercase().as_str() {
+        "toml" => Some(ConfigFormat::Toml),
+        "json" => Some(ConfigFormat::Json),
+        "yaml" | "yml" => Some(ConfigFormat::Yaml),
+        _ => None,
+    }
+}
+
+/// Device configuration, see `hal::DeviceConfig`
+#[pyclass(name = "DeviceConfig")]
+pub struct PyDeviceConfig {
+    pub inner: DeviceConfig,
+}
+
+#[pymethods]
+impl PyDeviceConfig {
+    /// Load a device configuration file, selecting the format by extension
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        let inner = hal::load_config(path).map_err(py_err)?;
+        Ok(Self { inner })
+    }
+
+    /// Parse device configuration data in the provided format (toml, json or yaml)
+    #[staticmethod]
+    #[pyo3(signature = (data, format = "toml"))]
+    fn parse(data: &str, format: &str) -> PyResult<Self> {
+        let f = config_format(format)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown config format '{}'", format)))?;
+
+        let inner = hal::config::parse_config("<string>", data, f).map_err(py_err)?;
+        Ok(Self { inner })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.inner)
+    }
+}
+
+/// Object-safe device operations, implemented for all `Hal` types
+trait DeviceOps {
+    fn transfer(&mut self, data: &mut [u8]) -> Result<(), String>;
+    fn write(&mut self, data: &[u8]) -> Result<(), String>;
+    fn prefix_read(&mut self, prefix: &[u8], data: &mut [u8]) -> Result<(), String>;
+    fn prefix_write(&mut self, prefix: &[u8], data: &[u8]) -> Result<(), String>;
+    fn get_busy(&mut self) -> Result<bool, String>;
+    fn get_ready(&mut self) -> Result<bool, String>;
+    fn set_reset(&mut self, state: PinState) -> Result<(), String>;
+    fn delay_ms(&mut self, ms: u32);
+}
+
+impl<T> DeviceOps for T
+where
+    T: SpiDevice<u8> + Busy + Ready + Reset + DelayUs,
+    <T as embedded_hal::spi::ErrorType>::Error: core::fmt::Debug,
+    <T as Busy>::Error: core::fmt::Debug,
+    <T as Ready>::Error: core::fmt::Debug,
+    <T as Reset>::Error: core::fmt::Debug,
+{
+    fn transfer(&mut self, data: &mut [u8]) -> Result<(), String> {
+        SpiDevice::transfer_in_place(self, data).map_err(|e| format!("{:?}", e))
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        SpiDevice::write(self, data).map_err(|e| format!("{:?}", e))
+    }
+
+    fn prefix_read(&mut self, prefix: &[u8], data: &mut [u8]) -> Result<(), String> {
+        PrefixRead::prefix_read(self, prefix, data).map_err(|e| format!("{:?}", e))
+    }
+
+    fn prefix_write(&mut self, prefix: &[u8], data: &[u8]) -> Result<(), String> {
+        PrefixWrite::prefix_write(self, prefix, data).map_err(|e| format!("{:?}", e))
+    }
+
+    fn get_busy(&mut self) -> Result<bool, String> {
+        Busy::get_busy(self)
+            .map(|s| s == PinState::High)
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    fn get_ready(&mut self) -> Result<bool, String> {
+        Ready::get_ready(self)
+            .map(|s| s == PinState::High)
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    fn set_reset(&mut self, state: PinState) -> Result<(), String> {
+        Reset::set_reset(self, state).map_err(|e| format!("{:?}", e))
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        // Single delay so mock expectations match `MockTransaction::delay_ms`
+        DelayUs::delay_us(self, ms.saturating_mul(1000))
+    }
+}
+
+/// Device wrapped by a python `Hal`
+enum Device {
+    Hal(HalInst),
+    Mock(mock::Spi),
+}
+
+impl Device {
+    fn ops(&mut self) -> &mut dyn DeviceOps {
+        match self {
+            Device::Hal(h) => h,
+            Device::Mock(s) => s,
+        }
+    }
+}
+
+/// HAL device, wrapping a `hal::HalInst` or a mock SPI device
+#[pyclass(name = "Hal", unsendable)]
+pub struct PyHal {
+    device: Device,
+}
+
+impl PyHal {
+    fn mock_spi(&self) -> PyResult<&mock::Spi> {
+        match &self.device {
+            Device::Mock(s) => Ok(s),
+            _ => Err(PyValueError::new_err("Expected a mock device")),
+        }
+    }
+
+    /// Poll an input until it reaches the expected state or the timeout elapses
+    fn wait_for<F>(
+        &mut self,
+        mut get: F,
+        state: bool,
+        timeout_ms: u64,
+        poll_ms: u32,
+    ) -> PyResult<bool>
+    where
+        F: FnMut(&mut dyn DeviceOps) -> Result<bool, String>,
+    {
+        let timeout = Duration::from_millis(timeout_ms);
+        let start = Instant::now();
+
+        loop {
+            if get(self.device.ops()).map_err(py_err)? == state {
+                return Ok(true);
+            }
+
+            if start.elapsed() >= timeout {
+                return Ok(false);
+            }
+
+            self.device.ops().delay_ms(poll_ms);
+        }
+    }
+}
+
+#[pymethods]
+impl PyHal {
+    /// Load a HAL instance from the provided device configuration
+    #[new]
+    fn new(config: &PyDeviceConfig) -> PyResult<Self> {
+        let h = HalInst::load(&config.inner).map_err(py_err)?;
+
+        Ok(Self {
+            device: Device::Hal(h),
+        })
+    }
+
+    /// Transfer data, returning the bytes read
+    fn transfer(&mut self, py: Python<'_>, data: Vec<u8>) -> PyResult<PyObject> {
+        let mut buff = data;
+        self.device.ops().transfer(&mut buff).map_err(py_err)?;
+
+        Ok(PyBytes::new(py, &buff).into())
+    }
+
+    /// Write data
+    fn write(&mut self, data: Vec<u8>) -> PyResult<()> {
+        self.device.ops().write(&data).map_err(py_err)
+    }
+
+    /// Write the prefix then read `length` bytes
+    fn prefix_read(
+        &mut self,
+        py: Python<'_>,
+        prefix: Vec<u8>,
+        length: usize,
+    ) -> PyResult<PyObject> {
+        let mut buff = vec![0u8; length];
+        self.device
+            .ops()
+            .prefix_read(&prefix, &mut buff)
+            .map_err(py_err)?;
+
+        Ok(PyBytes::new(py, &buff).into())
+    }
+
+    /// Write the prefix then the data
+    fn prefix_write(&mut self, prefix: Vec<u8>, data: Vec<u8>) -> PyResult<()> {
+        self.device
+            .ops()
+            .prefix_write(&prefix, &data)
+            .map_err(py_err)
+    }
+
+    /// Fetch the busy pin state
+    fn get_busy(&mut self) -> PyResult<bool> {
+        self.device.ops().get_busy().map_err(py_err)
+    }
+
+    /// Fetch the ready pin state
+    fn get_ready(&mut self) -> PyResult<bool> {
+        self.device.ops().get_ready().map_err(py_err)
+    }
+
+    /// Set the reset pin state
+    fn set_reset(&mut self, value: bool) -> PyResult<()> {
+        self.device
+            .ops()
+            .set_reset(pin_state(value))
+            .map_err(py_err)
+    }
+
+    /// Pulse the reset pin low, then wait for the device to start
+    #[pyo3(signature = (pulse_ms = 10, wait_ms = 10))]
+    fn reset(&mut self, pulse_ms: u32, wait_ms: u32) -> PyResult<()> {
+        let d = self.device.ops();
+
+        d.set_reset(PinState::Low).map_err(py_err)?;
+        d.delay_ms(pulse_ms);
+        d.set_reset(PinState::High).map_err(py_err)?;
+        d.delay_ms(wait_ms);
+
+        Ok(())
+    }
+
+    /// Wait for the busy pin to clear, returning false on timeout
+    #[pyo3(signature = (timeout_ms, poll_ms = 1))]
+    fn wait_busy(&mut self, timeout_ms: u64, poll_ms: u32) -> PyResult<bool> {
+        self.wait_for(|d| d.get_busy(), false, timeout_ms, poll_ms)
+    }
+
+    /// Wait for the ready pin to be set, returning false on timeout
+    #[pyo3(signature = (timeout_ms, poll_ms = 1))]
+    fn wait_ready(&mut self, timeout_ms: u64, poll_ms: u32) -> PyResult<bool> {
+        self.wait_for(|d| d.get_ready(), true, timeout_ms, poll_ms)
+    }
+
+    /// Delay for the provided number of milliseconds
+    fn delay_ms(&mut self, ms: u32) {
+        self.device.ops().delay_ms(ms)
+    }
+
+    /// Read a pin by name (busy or ready)
+    fn get_pin(&mut self, name: &str) -> PyResult<bool> {
+        let h = match &mut self.device {
+            Device::Hal(h) => h,
+            Device::Mock(_) => return Err(py_err("Named pins are not supported by mock devices")),
+        };
+
+        let p = match name {
+            "busy" => &h.pins.busy,
+            "ready" => &h.pins.ready,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown input pin '{}'",
+                    name
+                )))
+            }
+        };
+
+        p.is_high().map_err(py_err)
+    }
+
+    /// Write a pin by name (cs, reset, led0 or led1)
+    fn set_pin(&mut self, name: &str, value: bool) -> PyResult<()> {
+        let h = match &mut self.device {
+            Device::Hal(h) => h,
+            Device::Mock(_) => return Err(py_err("Named pins are not supported by mock devices")),
+        };
+
+        let p = match name {
+            "cs" => &mut h.pins.cs,
+            "reset" => &mut h.pins.reset,
+            "led0" => &mut h.pins.led0,
+            "led1" => &mut h.pins.led1,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown output pin '{}'",
+                    name
+                )))
+            }
+        };
+
+        let r = match value {
+            true => p.set_high(),
+            false => p.set_low(),
+        };
+
+        r.map_err(py_err)
+    }
+}
+
+/// Mock expectation, see `mock::MockTransaction`
+#[pyclass(name = "MockTransaction")]
+#[derive(Clone)]
+pub struct PyMockTransaction {
+    inner: MockTransaction,
+}
+
+#[pymethods]
+impl PyMockTransaction {
+    /// Expect a transfer writing `outgoing` and reading `incoming`
+    #[staticmethod]
+    fn transfer(spi: &PyHal, outgoing: Vec<u8>, incoming: Vec<u8>) -> PyResult<Self> {
+        let inner = MockTransaction::transfer(spi.mock_spi()?, outgoing, incoming);
+        Ok(Self { inner })
+    }
+
+    /// Expect a write
+    #[staticmethod]
+    fn write(spi: &PyHal, data: Vec<u8>) -> PyResult<Self> {
+        let inner = MockTransaction::write(spi.mock_spi()?, data);
+        Ok(Self { inner })
+    }
+
+    /// Expect a prefixed write
+    #[staticmethod]
+    fn prefix_write(spi: &PyHal, prefix: Vec<u8>, data: Vec<u8>) -> PyResult<Self> {
+        let inner = MockTransaction::spi_exec(
+            spi.mock_spi()?,
+            [MockExec::SpiWrite(prefix), MockExec::SpiWrite(data)],
+        );
+        Ok(Self { inner })
+    }
+
+    /// Expect a busy pin read, returning the provided value
+    #[staticmethod]
+    fn busy(spi: &PyHal, value: bool) -> PyResult<Self> {
+        let inner = MockTransaction::busy(spi.mock_spi()?, pin_state(value));
+        Ok(Self { inner })
+    }
+
+    /// Expect a ready pin read, returning the provided value
+    #[staticmethod]
+    fn ready(spi: &PyHal, value: bool) -> PyResult<Self> {
+        let inner = MockTransaction::ready(spi.mock_spi()?, pin_state(value));
+        Ok(Self { inner })
+    }
+
+    /// Expect a reset pin write
+    #[staticmethod]
+    fn reset(spi: &PyHal, value: bool) -> PyResult<Self> {
+        let inner = MockTransaction::reset(spi.mock_spi()?, pin_state(value));
+        Ok(Self { inner })
+    }
+
+    /// Expect a delay
+    #[staticmethod]
+    fn delay_ms(ms: u32) -> Self {
+        Self {
+            inner: MockTransaction::delay_ms(ms),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.inner)
+    }
+}
+
+/// Mock instance for testing, see `mock::Mock`
+#[pyclass(name = "Mock", unsendable)]
+pub struct PyMock {
+    inner: mock::Mock,
+}
+
+#[pymethods]
+impl PyMock {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: mock::Mock::new(),
+        }
+    }
+
+    /// Create a mock SPI device
+    fn spi(&mut self) -> PyHal {
+        PyHal {
+            device: Device::Mock(self.inner.spi()),
+        }
+    }
+
+    /// Set expectations, replacing any previous expectations
+    fn expect(&mut self, transactions: Vec<PyMockTransaction>) {
+        let t: Vec<_> = transactions.into_iter().map(|t| t.inner).collect();
+        self.inner.expect(t);
+    }
+
+    /// Check the expectations were met, raising an `AssertionError` otherwise
+    fn finalise(&self) -> PyResult<()> {
+        catch_unwind(AssertUnwindSafe(|| self.inner.finalise())).map_err(|p| {
+            let message = match (p.downcast_ref::<&str>(), p.downcast_ref::<String>()) {
+                (Some(s), _) => s.to_string(),
+                (_, Some(s)) => s.clone(),
+                _ => "Mock expectations not met".to_string(),
+            };
+            PyAssertionError::new_err(message)
+        })
+    }
+}
+
+/// Python module definition
+#[pymodule]
+#[pyo3(name = "driver_pal")]
+fn module(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("DriverPalError", py.get_type::<DriverPalError>())?;
+    m.add_class::<PyDeviceConfig>()?;
+    m.add_class::<PyHal>()?;
+    m.add_class::<PyMock>()?;
+    m.add_class::<PyMockTransaction>()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_config_format() {
+        assert_eq!(config_format("TOML"), Some(ConfigFormat::Toml));
+        assert_eq!(config_format("yml"), Some(ConfigFormat::Yaml));
+        assert_eq!(config_format("ini"), None);
+    }
+}
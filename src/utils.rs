@@ -1,188 +1,145 @@
+//! Convenience helpers for driver utilities
+//!
+//! `UtilOptions` collects the common command line options for driver utilities
+//! (an optional configuration file, device and logging options), with loaders
+//! returning a `hal::HalInst` after checking the pins required by the driver.
 
-use std::fs::read_to_string;
 use std::string::{String, ToString};
 
-pub use serde::{de::DeserializeOwned, Deserialize};
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+
 pub use clap::Parser;
 
-pub use simplelog::{LevelFilter, TermLogger};
-
-pub use embedded_hal::digital::v2::{InputPin, OutputPin};
-pub use linux_embedded_hal::sysfs_gpio::{Direction, Error as PinError};
-pub use linux_embedded_hal::{spidev, Delay, Pin as Pindev, Spidev, spidev::SpiModeFlags};
-
-use crate::wrapper::Wrapper;
-
-/// Generic device configuration structure for SPI drivers
-#[derive(Debug, Parser, Deserialize)]
-pub struct DeviceConfig {
-    /// Spi device
-    #[clap(
-        short = "d",
-        long = "spi-dev",
-        default_value = "/dev/spidev0.0",
-        env = "SPI_DEV"
-    )]
-    spi: String,
-
-    /// Baud rate setting
-    #[clap(
-        short = "b",
-        long = "spi-baud",
-        default_value = "1000000",
-        env = "SPI_BAUD"
-    )]
-    baud: u32,
-
-    /// Chip Select (output) pin
-    #[clap(long = "cs-pin", default_value = "16", env = "CS_PIN")]
-    chip_select: u64,
-
-    /// Reset (output) pin
-    #[clap(long = "reset-pin", default_value = "17", env = "RESET_PIN")]
-    reset: u64,
-
-    /// Busy (input) pin
-    #[clap(long = "busy-pin", env = "BUSY_PIN")]
-    busy: Option<u64>,
-
-    /// Ready (input) pin
-    #[clap(long = "ready-pin")]
-    ready: Option<u64>,
-}
+pub use crate::hal::{
+    load_config, DeviceConfig, HalDelay, HalError, HalInst, LevelFilter, LogConfig,
+};
 
-impl DeviceConfig {
-    /// Load without busy or ready pins
-    pub fn load_base(&self) -> Wrapper<Spidev, std::io::Error, Pindev, (), (), Pindev, PinError, Delay> {
-        // Load SPI peripheral
-        let spi = load_spi(&self.spi, self.baud, SpiModeFlags::SPI_MODE_0);
+/// Common command line options for driver utilities
+#[derive(Debug, Parser)]
+pub struct UtilOptions {
+    /// Device configuration file (TOML, JSON or YAML),
+    /// explicitly provided device options override file values
+    #[clap(long, env = "DEVICE_CONFIG")]
+    pub config: Option<String>,
 
-        // Setup CS pin
-        let mut cs = load_pin(self.chip_select, Direction::Out);
-        cs.set_high().unwrap();
+    #[clap(flatten)]
+    pub device: DeviceConfig,
 
-        // Setup reset pin
-        let reset = load_pin(self.reset, Direction::Out);
+    #[clap(flatten)]
+    pub log: LogConfig,
+}
 
-        Wrapper::new(spi, cs, (), (), reset, Delay {})
+impl UtilOptions {
+    /// Parse options from the command line, exiting with usage on error
+    pub fn from_args() -> Result<Self, HalError> {
+        let matches = Self::command().get_matches();
+        Self::from_matches(&matches)
     }
 
-    /// Load with busy pin
-    pub fn load_with_busy(
-        &self,
-    ) -> Wrapper<Spidev, std::io::Error, Pindev, Pindev, (), Pindev, PinError, Delay> {
-        // Load SPI peripheral
-        let spi = load_spi(&self.spi, self.baud, SpiModeFlags::SPI_MODE_0);
+    /// Load options from parsed arguments, merging any configuration file
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, HalError> {
+        let mut o =
+            Self::from_arg_matches(matches).map_err(|e| HalError::InvalidConfig(e.to_string()))?;
 
-        // Setup CS pin
-        let mut cs = load_pin(self.chip_select, Direction::Out);
-        cs.set_high().unwrap();
+        if let Some(f) = &o.config {
+            o.device = DeviceConfig::load_with_overrides(f, matches)?;
+        }
 
-        // Setup reset pin
-        let reset = load_pin(self.reset, Direction::Out);
-
-        // Setup optional pins
-        let busy = self.busy.map(|p| load_pin(p, Direction::Out)).unwrap();
-
-        Wrapper::new(spi, cs, busy, (), reset, Delay {})
+        Ok(o)
     }
 
-    /// Load with ready pin
-    pub fn load_with_ready(
-        &self,
-    ) -> Wrapper<Spidev, std::io::Error, Pindev, (), Pindev, Pindev, PinError, Delay> {
-        // Load SPI peripheral
-        let spi = load_spi(&self.spi, self.baud, SpiModeFlags::SPI_MODE_0);
-
-        // Setup CS pin
-        let mut cs = load_pin(self.chip_select, Direction::Out);
-        cs.set_high().unwrap();
-
-        // Setup reset pin
-        let reset = load_pin(self.reset, Direction::Out);
-
-        // Setup optional pins
-        let ready = self.ready.map(|p| load_pin(p, Direction::In)).unwrap();
-
-        Wrapper::new(spi, cs, (), ready, reset, Delay {})
+    /// Initialise logging and load the configured device
+    pub fn init(&self) -> Result<HalInst, HalError> {
+        self.log.init();
+        load(&self.device)
     }
+}
 
-    /// Load with busy and ready pins
-    pub fn load_with_busy_ready(
-        &self,
-    ) -> Wrapper<Spidev, std::io::Error, Pindev, Pindev, Pindev, Pindev, PinError, Delay> {
-        // Load SPI peripheral
-        let spi = load_spi(&self.spi, self.baud, SpiModeFlags::SPI_MODE_0);
-
-        // Setup CS pin
-        let mut cs = load_pin(self.chip_select, Direction::Out);
-        cs.set_high().unwrap();
-
-        // Setup reset pin
-        let reset = load_pin(self.reset, Direction::Out);
-
-        // Setup optional pins
-        let busy = self.busy.map(|p| load_pin(p, Direction::Out)).unwrap();
-        let ready = self.ready.map(|p| load_pin(p, Direction::In)).unwrap();
+/// Check the configuration provides the busy and / or ready pins where required
+pub fn check_pins(config: &DeviceConfig, busy: bool, ready: bool) -> Result<(), HalError> {
+    let missing = match (&config.pins.busy, &config.pins.ready) {
+        (None, _) if busy => "busy",
+        (_, None) if ready => "ready",
+        _ => return Ok(()),
+    };
+
+    Err(HalError::InvalidConfig(format!(
+        "Driver requires a {} pin (see --{}-pin)",
+        missing, missing
+    )))
+}
 
-        Wrapper::new(spi, cs, busy, ready, reset, Delay {})
-    }
+/// Load a device without busy or ready pins
+pub fn load(config: &DeviceConfig) -> Result<HalInst, HalError> {
+    HalInst::load(config)
 }
 
-#[derive(Debug, Parser)]
-pub struct LogConfig {
-    #[clap(long = "log-level", default_value = "info")]
-    /// Enable verbose logging
-    level: LevelFilter,
+/// Load a device with a busy (input) pin
+pub fn load_with_busy(config: &DeviceConfig) -> Result<HalInst, HalError> {
+    check_pins(config, true, false)?;
+    HalInst::load(config)
 }
 
-/// Load an SPI device using the provided configuration
-pub fn load_spi(path: &str, baud: u32, mode: spidev::SpiModeFlags) -> Spidev {
-    debug!(
-        "Conecting to spi: {} at {} baud with mode: {:?}",
-        path, baud, mode
-    );
+/// Load a device with a ready (input) pin
+pub fn load_with_ready(config: &DeviceConfig) -> Result<HalInst, HalError> {
+    check_pins(config, false, true)?;
+    HalInst::load(config)
+}
 
-    let mut spi = Spidev::open(path).expect("error opening spi device");
+/// Load a device with busy and ready (input) pins
+pub fn load_with_busy_ready(config: &DeviceConfig) -> Result<HalInst, HalError> {
+    check_pins(config, true, true)?;
+    HalInst::load(config)
+}
 
-    let mut config = spidev::SpidevOptions::new();
-    config.mode(SpiModeFlags::SPI_MODE_0 | SpiModeFlags::SPI_NO_CS);
-    config.max_speed_hz(baud);
-    spi.configure(&config)
-        .expect("error configuring spi device");
+/// Load a device from a configuration file
+pub fn load_file(file: &str) -> Result<HalInst, HalError> {
+    let config: DeviceConfig = load_config(file)?;
+    HalInst::load(&config)
+}
 
-    spi
+/// Fetch a delay implementation
+pub fn delay() -> HalDelay {
+    HalDelay::new()
 }
 
-/// Load a Pin using the provided configuration
-pub fn load_pin(index: u64, direction: Direction) -> Pindev {
-    debug!(
-        "Connecting to pin: {} with direction: {:?}",
-        index, direction
-    );
+#[cfg(test)]
+mod test {
+    use crate::hal::config::{parse_config, ConfigFormat};
 
-    let p = Pindev::new(index);
-    p.export().expect("error exporting cs pin");
-    p.set_direction(direction)
-        .expect("error setting cs pin direction");
+    use super::*;
 
-    p
-}
+    #[test]
+    fn test_check_pins() {
+        let c: DeviceConfig = parse_config("a.toml", "busy = 5", ConfigFormat::Toml).unwrap();
 
-/// Initialise logging
-pub fn init_logging(level: LevelFilter) {
-    TermLogger::init(level, simplelog::Config::default()).unwrap();
-}
+        assert!(check_pins(&c, true, false).is_ok());
+        assert!(check_pins(&c, false, true).is_err());
+        assert!(check_pins(&c, true, true).is_err());
 
-/// Load a configuration file
-pub fn load_config<T>(file: &str) -> T
-where
-    T: DeserializeOwned,
-{
-    let d = read_to_string(file).expect("error reading file");
-    toml::from_str(&d).expect("error parsing toml file")
-}
+        // Required pins are checked before any hardware is opened
+        assert!(load_with_ready(&c).is_err());
+    }
 
-pub fn delay() -> Delay {
-    Delay {}
+    #[test]
+    fn test_from_matches() {
+        let file = std::env::temp_dir().join("driver-pal-utils-test.toml");
+        std::fs::write(&file, "spi_dev = \"/dev/spidev1.0\"\nbusy = 5\n").unwrap();
+
+        let matches = UtilOptions::command()
+            .try_get_matches_from([
+                "test",
+                "--config",
+                file.to_str().unwrap(),
+                "--busy-pin",
+                "6",
+            ])
+            .unwrap();
+        let o = UtilOptions::from_matches(&matches).unwrap();
+
+        assert_eq!(o.device.spi_dev.as_deref(), Some("/dev/spidev1.0"));
+        assert_eq!(o.device.pins.busy.map(|p| p.index), Some(6));
+
+        std::fs::remove_file(file).unwrap();
+    }
 }
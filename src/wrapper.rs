@@ -3,10 +3,52 @@
 //! and `embedded_hal::digital::v2::OutputPin` to provide a transactional API for SPI transactions.
 
 use embedded_hal::delay::DelayUs;
-use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::digital::{ErrorKind as PinErrorKind, InputPin, OutputPin};
 use embedded_hal::spi::{Operation, SpiDevice};
 
-use crate::{Busy, Error, ManagedChipSelect, PinState, Ready, Reset};
+use crate::{Busy, Error, ManagedChipSelect, PinRole, PinState, Ready, Reset};
+
+/// Wrapper error type, pin errors are reduced to a `PinFault` and tagged with the pin role
+/// so wrappers over mixed pin types share a single error type
+pub type WrapperError<Spi> = Error<<Spi as embedded_hal::spi::ErrorType>::Error, PinFault>;
+
+/// Pin failure retaining the error kind and (with `std`) a description of the cause
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinFault {
+    /// Kind of the underlying pin error
+    pub kind: PinErrorKind,
+    /// Debug representation of the underlying pin error
+    #[cfg(feature = "std")]
+    pub cause: std::string::String,
+}
+
+impl PinFault {
+    /// Create a pin fault from an underlying pin error
+    pub fn new<E: embedded_hal::digital::Error>(e: E) -> Self {
+        Self {
+            kind: e.kind(),
+            #[cfg(feature = "std")]
+            cause: std::format!("{:?}", e),
+        }
+    }
+}
+
+impl embedded_hal::digital::Error for PinFault {
+    fn kind(&self) -> PinErrorKind {
+        self.kind
+    }
+}
+
+/// Map a pin error to a wrapper error for the provided pin role
+fn pin_error<SpiError, E>(role: PinRole) -> impl Fn(E) -> Error<SpiError, PinFault>
+where
+    E: embedded_hal::digital::Error,
+{
+    move |e| {
+        debug!("{:?} pin error: {:?}", role, e);
+        Error::Pin(role, PinFault::new(e))
+    }
+}
 
 /// Wrapper provides a wrapper around an SPI object with Chip Select management
 pub struct Wrapper<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay> {
@@ -64,26 +106,16 @@ impl<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay> embedded_hal::spi::ErrorTyp
     for Wrapper<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay>
 where
     Spi: embedded_hal::spi::ErrorType,
-    CsPin: embedded_hal::digital::ErrorType,
-    Delay: DelayUs,
 {
-    type Error = Error<
-        <Spi as embedded_hal::spi::ErrorType>::Error,
-        <CsPin as embedded_hal::digital::ErrorType>::Error,
-    >;
+    type Error = WrapperError<Spi>;
 }
 
 impl<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay> embedded_hal::digital::ErrorType
     for Wrapper<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay>
 where
     Spi: embedded_hal::spi::ErrorType,
-    CsPin: embedded_hal::digital::ErrorType,
-    Delay: DelayUs,
 {
-    type Error = Error<
-        <Spi as embedded_hal::spi::ErrorType>::Error,
-        <CsPin as embedded_hal::digital::ErrorType>::Error,
-    >;
+    type Error = WrapperError<Spi>;
 }
 
 impl<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay> SpiDevice<u8>
@@ -94,34 +126,34 @@ where
     Delay: DelayUs,
 {
     fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
-        self.cs.set_low().map_err(Error::Pin)?;
+        self.cs.set_low().map_err(pin_error(PinRole::ChipSelect))?;
 
         let r = self.spi.transaction(operations).map_err(Error::Spi);
 
-        self.cs.set_high().map_err(Error::Pin)?;
+        self.cs.set_high().map_err(pin_error(PinRole::ChipSelect))?;
 
         r
     }
 
     /// spi write implementation managing the CS pin
     fn write<'w>(&mut self, data: &'w [u8]) -> Result<(), Self::Error> {
-        self.cs.set_low().map_err(Error::Pin)?;
+        self.cs.set_low().map_err(pin_error(PinRole::ChipSelect))?;
 
         let r = self.spi.write(data).map_err(Error::Spi);
 
-        self.cs.set_high().map_err(Error::Pin)?;
+        self.cs.set_high().map_err(pin_error(PinRole::ChipSelect))?;
 
         r
     }
 
     fn transfer_in_place<'w>(&mut self, data: &'w mut [u8]) -> Result<(), Self::Error> {
-        self.cs.set_low().map_err(Error::Pin)?;
+        self.cs.set_low().map_err(pin_error(PinRole::ChipSelect))?;
 
-        self.spi.transfer_in_place(data).map_err(Error::Spi)?;
+        let r = self.spi.transfer_in_place(data).map_err(Error::Spi);
 
-        self.cs.set_high().map_err(Error::Pin)?;
+        self.cs.set_high().map_err(pin_error(PinRole::ChipSelect))?;
 
-        Ok(())
+        r
     }
 }
 
@@ -129,17 +161,18 @@ where
 impl<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay> Reset
     for Wrapper<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay>
 where
+    Spi: embedded_hal::spi::ErrorType,
     ResetPin: OutputPin,
 {
-    type Error = <ResetPin as embedded_hal::digital::ErrorType>::Error;
+    type Error = WrapperError<Spi>;
 
     /// Set the reset pin state
     fn set_reset(&mut self, state: PinState) -> Result<(), Self::Error> {
-        match state {
-            PinState::High => self.reset.set_high()?,
-            PinState::Low => self.reset.set_low()?,
+        let r = match state {
+            PinState::High => self.reset.set_high(),
+            PinState::Low => self.reset.set_low(),
         };
-        Ok(())
+        r.map_err(pin_error(PinRole::Reset))
     }
 }
 
@@ -147,13 +180,14 @@ where
 impl<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay> Busy
     for Wrapper<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay>
 where
+    Spi: embedded_hal::spi::ErrorType,
     BusyPin: InputPin,
 {
-    type Error = <BusyPin as embedded_hal::digital::ErrorType>::Error;
+    type Error = WrapperError<Spi>;
 
     /// Fetch the busy pin state
     fn get_busy(&mut self) -> Result<PinState, Self::Error> {
-        match self.busy.is_high()? {
+        match self.busy.is_high().map_err(pin_error(PinRole::Busy))? {
             true => Ok(PinState::High),
             false => Ok(PinState::Low),
         }
@@ -164,13 +198,14 @@ where
 impl<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay> Ready
     for Wrapper<Spi, CsPin, BusyPin, ReadyPin, ResetPin, Delay>
 where
+    Spi: embedded_hal::spi::ErrorType,
     ReadyPin: InputPin,
 {
-    type Error = <ReadyPin as embedded_hal::digital::ErrorType>::Error;
+    type Error = WrapperError<Spi>;
 
     /// Fetch the ready pin state
     fn get_ready(&mut self) -> Result<PinState, Self::Error> {
-        match self.ready.is_high()? {
+        match self.ready.is_high().map_err(pin_error(PinRole::Ready))? {
             true => Ok(PinState::High),
             false => Ok(PinState::Low),
         }
@@ -186,3 +221,62 @@ where
         self.delay.delay_us(us)
     }
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod test {
+    use std::vec;
+
+    use super::*;
+    use crate::mock::{Mock, MockTransaction, Spi};
+    use crate::Hal;
+
+    fn assert_hal<E, H: Hal<E>>(_h: &H) {}
+
+    #[test]
+    fn test_wrapper_hal() {
+        let mut m = Mock::new();
+        let spi = m.spi();
+        let (cs, reset, busy, ready) = (m.pin(), m.pin(), m.pin(), m.pin());
+        let delay = m.delay();
+
+        let mut w = Wrapper::new(
+            spi.clone(),
+            cs.clone(),
+            reset.clone(),
+            busy.clone(),
+            ready.clone(),
+            delay,
+        );
+
+        assert_hal::<WrapperError<Spi>, _>(&w);
+
+        m.expect(vec![
+            MockTransaction::set_low(&cs),
+            MockTransaction::write(&spi, vec![0xAA]),
+            MockTransaction::set_high(&cs),
+            MockTransaction::set_high(&reset),
+            MockTransaction::is_high(&busy, true),
+            MockTransaction::is_high(&ready, false),
+        ]);
+
+        w.write(&[0xAA]).unwrap();
+        w.set_reset(PinState::High).unwrap();
+        assert_eq!(w.get_busy().unwrap(), PinState::High);
+        assert_eq!(w.get_ready().unwrap(), PinState::Low);
+
+        m.finalise();
+    }
+
+    #[test]
+    fn test_pin_error_cause() {
+        let e = pin_error::<(), _>(PinRole::Busy)(crate::mock::PinError);
+
+        match e {
+            Error::Pin(PinRole::Busy, f) => {
+                assert_eq!(f.kind, PinErrorKind::Other);
+                assert_eq!(f.cause, "PinError");
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+}
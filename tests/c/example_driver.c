@@ -0,0 +1,52 @@
+/*
+ * Example C driver using the driver-pal function table, standing in for vendor
+ * drivers in `ffi::test::test_c_driver`. Resets the device, waits for busy to
+ * clear then reads the device ID register.
+ */
+
+#include <stdint.h>
+
+#include "driver_pal.h"
+
+#define EXAMPLE_REG_ID 0x00
+#define EXAMPLE_READ 0x80
+#define EXAMPLE_BUSY_RETRIES 10
+
+static int example_read_reg(const driver_pal_vtable_t *hal, uint8_t reg) {
+    uint8_t buff[2] = {EXAMPLE_READ | reg, 0x00};
+
+    int res = hal->spi_transfer(hal->ctx, buff, sizeof(buff));
+    if (res < 0) {
+        return res;
+    }
+
+    return buff[1];
+}
+
+/* Initialise the device, returning the device ID or a negative error */
+int example_driver_init(const driver_pal_vtable_t *hal) {
+    int res;
+
+    /* Reset device */
+    if ((res = hal->gpio_write(hal->ctx, DRIVER_PAL_PIN_RESET, 0)) < 0) {
+        return res;
+    }
+    hal->delay_ms(hal->ctx, 1);
+    if ((res = hal->gpio_write(hal->ctx, DRIVER_PAL_PIN_RESET, 1)) < 0) {
+        return res;
+    }
+
+    /* Wait for busy to clear */
+    for (int i = 0; i < EXAMPLE_BUSY_RETRIES; i++) {
+        res = hal->gpio_read(hal->ctx, DRIVER_PAL_PIN_BUSY);
+        if (res <= 0) {
+            break;
+        }
+        hal->delay_ms(hal->ctx, 1);
+    }
+    if (res != 0) {
+        return res < 0 ? res : DRIVER_PAL_ERR_TIMEOUT;
+    }
+
+    return example_read_reg(hal, EXAMPLE_REG_ID);
+}
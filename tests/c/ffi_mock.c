@@ -0,0 +1,60 @@
+/*
+ * C API test program, run against the mock backend by `ffi::test::test_c_api`
+ * which configures the expected transactions. Returns 0 on success, the failing
+ * driver-pal return code or -100 and below for unexpected values.
+ */
+
+#include <stdint.h>
+#include <string.h>
+
+#include "driver_pal.h"
+
+#define CHECK(x)          \
+    do {                  \
+        int res = (x);    \
+        if (res < 0) {    \
+            return res;   \
+        }                 \
+    } while (0)
+
+int driver_pal_test_mock(driver_pal_t *hal) {
+    /* Prefixed write */
+    const uint8_t write_prefix[] = {0x01};
+    const uint8_t write_data[] = {0xAA, 0xBB};
+    CHECK(driver_pal_spi_prefix_write(hal, write_prefix, sizeof(write_prefix), write_data, sizeof(write_data)));
+
+    /* Prefixed read */
+    const uint8_t read_prefix[] = {0x02};
+    uint8_t read_data[2] = {0};
+    CHECK(driver_pal_spi_prefix_read(hal, read_prefix, sizeof(read_prefix), read_data, sizeof(read_data)));
+
+    /* In place transfer */
+    uint8_t xfer[] = {0x11, 0x22};
+    const uint8_t xfer_in[] = {0x33, 0x44};
+    CHECK(driver_pal_spi_transfer(hal, xfer, sizeof(xfer)));
+    if (memcmp(xfer, xfer_in, sizeof(xfer)) != 0) {
+        return -100;
+    }
+
+    /* Pins */
+    if (driver_pal_get_busy(hal) != 1) {
+        return -101;
+    }
+    if (driver_pal_get_ready(hal) != 0) {
+        return -102;
+    }
+    CHECK(driver_pal_set_reset(hal, 1));
+
+    /* Delay */
+    CHECK(driver_pal_delay_us(hal, 100));
+
+    /* Invalid handles are rejected without touching the mock */
+    if (driver_pal_get_busy(NULL) != DRIVER_PAL_ERR_NULL) {
+        return -103;
+    }
+    if (strcmp(driver_pal_strerror(DRIVER_PAL_ERR_NULL), "Null handle or buffer") != 0) {
+        return -104;
+    }
+
+    return 0;
+}
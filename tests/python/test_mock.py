@@ -0,0 +1,63 @@
+"""Python binding tests against the mock backend, run with `maturin develop && pytest tests/python`"""
+
+import pytest
+
+import driver_pal
+from driver_pal import MockTransaction as T
+
+
+def test_transfer():
+    m = driver_pal.Mock()
+    spi = m.spi()
+
+    m.expect([
+        T.prefix_write(spi, [0x01], [0xAA, 0xBB]),
+        T.transfer(spi, [0x80, 0x00], [0x00, 0x5A]),
+    ])
+
+    spi.prefix_write([0x01], [0xAA, 0xBB])
+    assert spi.transfer(bytes([0x80, 0x00])) == bytes([0x00, 0x5A])
+
+    m.finalise()
+
+
+def test_reset_and_busy():
+    m = driver_pal.Mock()
+    spi = m.spi()
+
+    m.expect([
+        T.reset(spi, False),
+        T.delay_ms(10),
+        T.reset(spi, True),
+        T.delay_ms(10),
+        T.busy(spi, True),
+        T.delay_ms(1),
+        T.busy(spi, False),
+    ])
+
+    spi.reset()
+    assert spi.wait_busy(100)
+
+    m.finalise()
+
+
+def test_unmet_expectations():
+    m = driver_pal.Mock()
+    spi = m.spi()
+
+    m.expect([T.write(spi, [0x01])])
+    spi.write([0x02])
+
+    with pytest.raises(AssertionError):
+        m.finalise()
+
+
+def test_config():
+    c = driver_pal.DeviceConfig.parse('spi_dev = "/dev/spidev0.0"\nchip_select = 8\n')
+    assert "spidev0.0" in repr(c)
+
+    with pytest.raises(driver_pal.DriverPalError):
+        driver_pal.DeviceConfig.parse("baud = \"fast\"")
+
+    with pytest.raises(ValueError):
+        driver_pal.DeviceConfig.parse("", format="ini")